@@ -2,8 +2,21 @@ use crate::util::error::Error;
 
 pub enum Argument {
 	SetVersion(String),
+	SetLoader(String),
 	SetInstanceDir(String),
 	SetDataDir(String),
+	AddJavaPath(String),
+	SetServerDir(String),
+	SetMemory(String),
+	SetPreset(String),
+	SetAccount(String),
+	SetUsername(String),
+	SetAuthServer(String),
+	// Commands and their values ("server install 1.20.1")
+	Positional(String),
+	ListVersions,
+	UseOnlineAuth,
+	Detach,
 	GetHelp,
 	GetProgramVersion,
 }
@@ -13,8 +26,16 @@ impl Argument {
 		if let Some(current) = current {
 			match previous.as_str() {
 				"-l" | "--launch" => return Ok(Self::SetVersion(current)),
+				"--loader" => return Ok(Self::SetLoader(current)),
 				"-i" | "--instance-dir" => return Ok(Self::SetInstanceDir(current)),
 				"-d" | "--data-dir" => return Ok(Self::SetDataDir(current)),
+				"-j" | "--java" => return Ok(Self::AddJavaPath(current)),
+				"-s" | "--server-dir" => return Ok(Self::SetServerDir(current)),
+				"-m" | "--memory" => return Ok(Self::SetMemory(current)),
+				"--preset" => return Ok(Self::SetPreset(current)),
+				"-a" | "--account" => return Ok(Self::SetAccount(current)),
+				"-u" | "--username" => return Ok(Self::SetUsername(current)),
+				"--auth-server" => return Ok(Self::SetAuthServer(current)),
 
 				_ => return Err(Error::Default(format!("wrong argument: {previous}"))),
 			}
@@ -23,34 +44,52 @@ impl Argument {
 		match previous.as_str() {
 			"-h" | "--help" => Ok(Self::GetHelp),
 			"-v" | "--version" => Ok(Self::GetProgramVersion),
+			"--list" => Ok(Self::ListVersions),
+			"--online" => Ok(Self::UseOnlineAuth),
+			"--detach" => Ok(Self::Detach),
 
 			_ => Ok(Self::GetHelp),
 		}
 	}
 
+	// Arguments without value
+	fn is_switch(argument: &str) -> bool {
+		matches!(
+			argument,
+			"-h" | "--help" | "-v" | "--version" | "--list" | "--online" | "--detach"
+		)
+	}
+
 	pub fn get_parsed() -> Result<Vec<Self>, Error> {
 		let mut result: Vec<Self> = Default::default();
-		let arguments = std::env::args();
+		// First one is executable path
+		let arguments = std::env::args().skip(1);
 		result.reserve(arguments.len());
 
 		let mut previous = String::from("");
 
 		for argument in arguments {
 			if argument.starts_with("-") {
-				if previous.len() > 0 {
+				if !previous.is_empty() {
 					return Err(Error::Default(format!(
 						"value not provided for argument {previous}"
 					)));
 				}
 
-				previous = argument;
-			} else if previous.len() > 0 {
+				if Self::is_switch(&argument) {
+					result.push(Self::parse(&argument, None)?);
+				} else {
+					previous = argument;
+				}
+			} else if !previous.is_empty() {
 				result.push(Self::parse(&previous, Some(argument))?);
 				previous.clear();
+			} else {
+				result.push(Self::Positional(argument));
 			}
 		}
 
-		if previous.len() > 0 {
+		if !previous.is_empty() {
 			result.push(Self::parse(&previous, None)?);
 		}
 
@@ -60,7 +99,7 @@ impl Argument {
 	pub fn print_help_and_exit() -> Result<(), Error> {
 		let path = std::env::current_exe();
 		let current_exe = match path.as_ref() {
-			Ok(path) => match path.iter().last() {
+			Ok(path) => match path.iter().next_back() {
 				Some(exe) => exe.to_str().unwrap(),
 				None => "rostermine",
 			},
@@ -68,9 +107,26 @@ impl Argument {
 		};
 
 		println!("USAGE: {current_exe} -l [version id]",);
+		println!("       {current_exe} server install [version id] -s [path]");
+		println!("       {current_exe} server run -s [path] -m [memory]");
+		println!("       {current_exe} account add|list|remove [name]|default [name]");
+		println!("       {current_exe} ps");
+		println!("       {current_exe} stop [instance]");
+		println!("       {current_exe} deobf [crash report or log] -i [path]");
 		println!("-l\t--launch [version id] - Launch minecraft");
+		println!("\t--loader [name[:version]] - Modloader for game version (fabric, quilt, forge, neoforge)");
 		println!("-i\t--instance-dir [path] - Directory for game saves, mods, etc.");
 		println!("-d\t--data-dir [path]     - TODO");
+		println!("-j\t--java [path]         - Java home or executable to look at first");
+		println!("-s\t--server-dir [path]   - Directory of dedicated server");
+		println!("-m\t--memory [size]       - Game or server heap size (ex.: 4G, auto for game), saved into instance");
+		println!("\t--preset [name]       - JVM flags preset for game (default, g1, zgc)");
+		println!("\t--list                - List official and local versions (or loader versions with --loader)");
+		println!("\t--online              - Sign in with microsoft account before launch");
+		println!("-a\t--account [name]      - Saved account to play with (default one otherwise)");
+		println!("-u\t--username [name]     - Play offline with given name");
+		println!("\t--auth-server [url]   - Yggdrasil auth server for --online and \"account add\"");
+		println!("\t--detach              - Start game in background and exit, output goes to log");
 		println!("-h\t--help                - Help ;/");
 
 		std::process::exit(0);
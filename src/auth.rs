@@ -1 +1,26 @@
-struct AuthSession {}
\ No newline at end of file
+pub mod accounts;
+pub mod microsoft;
+pub mod offline;
+pub mod secrets;
+pub mod yggdrasil;
+
+/* AUTH
+* Game gets player identity through launch arguments:
+* ${auth_player_name}, ${auth_uuid}, ${auth_access_token}, ${auth_xuid}, ${user_type}
+* Sessions of third-party auth servers also need authlib-injector agent (see yggdrasil.rs)
+* Offline session has no token, game works only in singleplayer and on offline servers
+*/
+
+#[derive(Default, Debug, Clone)]
+pub struct AuthSession {
+	pub name: String,
+	pub uuid: String,
+	pub access_token: String,
+	pub xuid: String,
+	// "msa" for microsoft accounts, "legacy" for offline ones
+	pub user_type: String,
+	// Seconds, while access token is valid
+	pub expires_in: u64,
+	// Yggdrasil API root of third-party auth server
+	pub auth_server: Option<String>,
+}
@@ -0,0 +1,377 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::error::Error;
+
+use super::microsoft::MicrosoftAuth;
+use super::secrets::{SecretFile, SecretStorage};
+use super::yggdrasil::{Profile, YggdrasilAuth};
+use super::AuthSession;
+
+/* ACCOUNTS
+* data/accounts.json keeps public part of signed in accounts:
+* {
+*   "default": "[uuid]",
+*   "accounts": [ { "name", "uuid", "xuid", "userType", "expiresAt", "storage", "authServer" }, ... ]
+* }
+* Microsoft refresh token and game access token are secrets (see secrets.rs)
+* Game token lives for a day, so it's silently refreshed before launch
+* Accounts of third-party auth servers keep Yggdrasil client token instead of refresh one
+* and are validated on every launch, because their tokens have no known lifetime
+*/
+
+const ACCOUNTS_FILE: &str = "accounts.json";
+
+// Token shouldn't expire in the middle of joining a server
+const REFRESH_MARGIN_SECS: u64 = 300;
+
+pub struct AccountStore {
+	path: PathBuf,
+	secrets: SecretFile,
+	data: AccountsFile,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct AccountsFile {
+	default: Option<String>,
+	accounts: Vec<Account>,
+}
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Account {
+	pub name: String,
+	pub uuid: String,
+	pub xuid: String,
+	pub user_type: String,
+	// Unix time, when game access token expires
+	pub expires_at: u64,
+	pub storage: SecretStorage,
+	// Yggdrasil API root, microsoft account otherwise
+	pub auth_server: Option<String>,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct AccountSecret {
+	refresh_token: String,
+	access_token: String,
+}
+
+impl AccountStore {
+	pub fn load(data_dir: &str) -> Result<Self, Error> {
+		let path = Path::new(data_dir).join(ACCOUNTS_FILE);
+
+		let data = match path.exists() {
+			true => serde_json::from_str(&fs::read_to_string(&path)?)?,
+			false => Default::default(),
+		};
+
+		Ok(Self {
+			path,
+			secrets: SecretFile::new(data_dir),
+			data,
+		})
+	}
+
+	fn save(&self) -> Result<(), Error> {
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::write(&self.path, serde_json::to_string_pretty(&self.data)?)?;
+
+		Ok(())
+	}
+
+	pub fn get_accounts(&self) -> &[Account] {
+		&self.data.accounts
+	}
+
+	pub fn get_default(&self) -> Option<&Account> {
+		self.find(self.data.default.as_ref()?)
+	}
+
+	pub fn is_default(&self, account: &Account) -> bool {
+		self.data.default.as_ref() == Some(&account.uuid)
+	}
+
+	// By player name (case doesn't matter) or uuid
+	pub fn find(&self, name: &str) -> Option<&Account> {
+		self.data.accounts.iter().find(|account| {
+			account.uuid == name.replace('-', "") || account.name.eq_ignore_ascii_case(name)
+		})
+	}
+
+	// Account with the same uuid is replaced, first account becomes default
+	// Refresh token is microsoft one or Yggdrasil client token
+	pub fn add(&mut self, session: &AuthSession, refresh_token: &str) -> Result<(), Error> {
+		let secret = serde_json::to_string(&AccountSecret {
+			refresh_token: refresh_token.to_string(),
+			access_token: session.access_token.clone(),
+		})?;
+		let storage = SecretStorage::store(&session.uuid, &secret, &self.secrets)?;
+
+		self.data
+			.accounts
+			.retain(|account| account.uuid != session.uuid);
+		self.data.accounts.push(Account {
+			name: session.name.clone(),
+			uuid: session.uuid.clone(),
+			xuid: session.xuid.clone(),
+			user_type: session.user_type.clone(),
+			expires_at: Self::get_time() + session.expires_in,
+			storage,
+			auth_server: session.auth_server.clone(),
+		});
+
+		if self.get_default().is_none() {
+			self.data.default = Some(session.uuid.clone());
+		}
+
+		self.save()
+	}
+
+	pub fn remove(&mut self, name: &str) -> Result<Account, Error> {
+		let account = self.get(name)?;
+		account.storage.remove(&account.uuid, &self.secrets)?;
+
+		self.data
+			.accounts
+			.retain(|other| other.uuid != account.uuid);
+		if self.data.default.as_ref() == Some(&account.uuid) {
+			self.data.default = self
+				.data
+				.accounts
+				.first()
+				.map(|account| account.uuid.clone());
+		}
+
+		self.save()?;
+		Ok(account)
+	}
+
+	pub fn set_default(&mut self, name: &str) -> Result<Account, Error> {
+		let account = self.get(name)?;
+		self.data.default = Some(account.uuid.clone());
+
+		self.save()?;
+		Ok(account)
+	}
+
+	// Expired token is refreshed through microsoft refresh token
+	pub fn get_session(&mut self, name: &str, auth: &MicrosoftAuth) -> Result<AuthSession, Error> {
+		let account = self.get(name)?;
+		let secret: AccountSecret =
+			serde_json::from_str(account.storage.lookup(&account.uuid, &self.secrets)?.trim())?;
+
+		if let Some(api_root) = account.auth_server.as_deref() {
+			return self.get_yggdrasil_session(&account, secret, &YggdrasilAuth::new(api_root));
+		}
+
+		if Self::get_time() + REFRESH_MARGIN_SECS < account.expires_at {
+			return Ok(Self::get_saved_session(
+				&account,
+				secret,
+				account.expires_at - Self::get_time(),
+			));
+		}
+
+		println!("Refreshing session of {}. . .", account.name);
+		let token = match auth.refresh(&secret.refresh_token) {
+			Ok(token) => token,
+			// Singleplayer still works with old token
+			Err(Error::Download(e)) => {
+				println!("WARNING: session of {} isn't refreshed: {e}", account.name);
+				return Ok(Self::get_saved_session(&account, secret, 0));
+			}
+			Err(e) => {
+				return Err(Error::Default(format!(
+					"{e}. Sign in again with \"account add\""
+				)))
+			}
+		};
+
+		let session = auth.get_session(&token)?;
+		self.add(&session, &token.refresh_token)?;
+
+		Ok(session)
+	}
+
+	fn get_yggdrasil_session(
+		&mut self,
+		account: &Account,
+		secret: AccountSecret,
+		auth: &YggdrasilAuth,
+	) -> Result<AuthSession, Error> {
+		let session = match auth.validate(&secret.access_token, &secret.refresh_token) {
+			Ok(true) => auth.get_session(
+				&auth.get_profile(&account.uuid).unwrap_or(Profile {
+					id: account.uuid.clone(),
+					name: account.name.clone(),
+				}),
+				&secret.access_token,
+			),
+			Ok(false) => {
+				println!("Refreshing session of {}. . .", account.name);
+				auth.refresh(&secret.access_token, &secret.refresh_token, None)
+					.map_err(|e| {
+						Error::Default(format!(
+							"{e}. Sign in again with \"account add --auth-server {}\"",
+							auth.get_api_root()
+						))
+					})?
+			}
+			Err(Error::Download(e)) => {
+				println!("WARNING: session of {} isn't validated: {e}", account.name);
+				return Ok(Self::get_saved_session(account, secret, 0));
+			}
+			Err(e) => return Err(e),
+		};
+
+		if session.access_token != secret.access_token || session.name != account.name {
+			self.add(&session, &secret.refresh_token)?;
+		}
+		Ok(session)
+	}
+
+	fn get_saved_session(account: &Account, secret: AccountSecret, expires_in: u64) -> AuthSession {
+		AuthSession {
+			name: account.name.clone(),
+			uuid: account.uuid.clone(),
+			access_token: secret.access_token,
+			xuid: account.xuid.clone(),
+			user_type: account.user_type.clone(),
+			expires_in,
+			auth_server: account.auth_server.clone(),
+		}
+	}
+
+	fn get(&self, name: &str) -> Result<Account, Error> {
+		self.find(name)
+			.cloned()
+			.ok_or_else(|| Error::Default(format!("no account {name}. See \"account list\"")))
+	}
+
+	fn get_time() -> u64 {
+		SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|time| time.as_secs())
+			.unwrap_or_default()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::TcpListener;
+
+	use serde_json::json;
+
+	use super::*;
+	use crate::auth::microsoft::tests::{get_auth, get_game_token, start_server};
+	use crate::auth::microsoft::Endpoints;
+	use crate::util::testing::{get_temp_dir, Response};
+
+	const UUID: &str = "069a79f444e94726a5befca90e38aaf5";
+
+	// Saved account with token, which expires in given seconds
+	fn get_store(name: &str, expires_in: u64) -> AccountStore {
+		let data_dir = get_temp_dir(name);
+		let mut store = AccountStore::load(data_dir.to_str().unwrap()).unwrap();
+		store
+			.add(
+				&AuthSession {
+					name: String::from("Notch"),
+					uuid: String::from(UUID),
+					access_token: String::from("saved-access"),
+					xuid: String::from("1"),
+					user_type: String::from("msa"),
+					expires_in,
+					auth_server: None,
+				},
+				"saved-refresh",
+			)
+			.unwrap();
+		store
+	}
+
+	fn get_secret(store: &AccountStore) -> AccountSecret {
+		let account = store.get("Notch").unwrap();
+		serde_json::from_str(&account.storage.lookup(UUID, &store.secrets).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn valid_token_is_used_as_is() {
+		let server = start_server(|_| None);
+		let mut store = get_store("accounts-valid", 3600);
+
+		let session = store.get_session("Notch", &get_auth(&server)).unwrap();
+		assert_eq!(session.access_token, "saved-access");
+		assert!(session.expires_in > REFRESH_MARGIN_SECS);
+		assert!(server.get_requests().is_empty());
+	}
+
+	#[test]
+	fn token_is_refreshed_before_expiration() {
+		let server = start_server(|path| {
+			(path == "/token").then(|| {
+				Response::json(
+					200,
+					json!({ "access_token": "ms-access", "refresh_token": "new-refresh", "expires_in": 3600 }),
+				)
+			})
+		});
+		// Still valid, but not for long enough
+		let mut store = get_store("accounts-refresh", REFRESH_MARGIN_SECS - 60);
+
+		let session = store.get_session("Notch", &get_auth(&server)).unwrap();
+		assert_eq!(session.access_token, get_game_token());
+
+		let requests = server.get_requests();
+		assert_eq!(requests[0].path, "/token");
+		assert!(requests[0].body.contains("refresh_token=saved-refresh"));
+
+		// Refreshed tokens are saved
+		let secret = get_secret(&store);
+		assert_eq!(secret.refresh_token, "new-refresh");
+		assert_eq!(secret.access_token, get_game_token());
+
+		let reloaded = AccountStore::load(store.path.parent().unwrap().to_str().unwrap()).unwrap();
+		let account = reloaded.get("Notch").unwrap();
+		assert!(account.expires_at > AccountStore::get_time() + REFRESH_MARGIN_SECS);
+		assert_eq!(reloaded.get_accounts().len(), 1);
+	}
+
+	#[test]
+	fn saved_token_is_used_offline() {
+		// Port, which nobody listens to
+		let port = TcpListener::bind("127.0.0.1:0")
+			.unwrap()
+			.local_addr()
+			.unwrap()
+			.port();
+		let auth = MicrosoftAuth::new(Endpoints {
+			token: format!("http://127.0.0.1:{port}/token"),
+			..Default::default()
+		});
+		let mut store = get_store("accounts-offline", 0);
+
+		let session = store.get_session("Notch", &auth).unwrap();
+		assert_eq!(session.access_token, "saved-access");
+		assert_eq!(session.expires_in, 0);
+		assert_eq!(get_secret(&store).refresh_token, "saved-refresh");
+	}
+
+	#[test]
+	fn rejected_refresh_asks_to_sign_in() {
+		let server = start_server(|path| {
+			(path == "/token").then(|| Response::json(400, json!({ "error": "invalid_grant" })))
+		});
+		let mut store = get_store("accounts-rejected", 0);
+
+		let error = store.get_session("Notch", &get_auth(&server)).unwrap_err();
+		assert!(error.to_string().contains("Sign in again"));
+		assert_eq!(get_secret(&store).access_token, "saved-access");
+	}
+}
@@ -0,0 +1,613 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use reqwest::blocking::{Client, Response};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::util::error::Error;
+
+use super::AuthSession;
+
+/* MICROSOFT
+* Minecraft account is logged in through chain of services:
+* - device code: user opens verification page and enters code, launcher polls token endpoint
+* - Xbox Live: microsoft access token -> xbox user token and user hash (uhs)
+* - XSTS: user token -> token for minecraft services
+* - Minecraft services: "XBL3.0 x=[uhs];[xsts token]" -> game access token (JWT with xuid)
+* - entitlements: does account own the game
+* - profile: player name and uuid
+* Every endpoint (and client id) may be replaced in optional data/auth_endpoints.json,
+* for example with local mock server
+*/
+
+const ENDPOINTS_PATH: &str = "data/auth_endpoints.json";
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+// Device code intervals are given in seconds
+const POLL_UNIT: Duration = Duration::from_secs(1);
+
+// XSTS errors, that user can fix
+const XERR_NO_XBOX_ACCOUNT: u64 = 2148916233;
+const XERR_COUNTRY_BANNED: u64 = 2148916235;
+const XERR_CHILD_ACCOUNT: u64 = 2148916238;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Endpoints {
+	pub client_id: String,
+	pub scope: String,
+	// Live tokens are passed to xbox as is, azure ones - with "d=" prefix
+	pub rps_ticket_prefix: String,
+	pub device_code: String,
+	pub token: String,
+	pub xbox_user: String,
+	pub xsts: String,
+	pub minecraft_login: String,
+	pub entitlements: String,
+	pub profile: String,
+}
+
+// Microsoft part of login, that is able to restore the whole session
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MicrosoftToken {
+	pub access_token: String,
+	pub refresh_token: String,
+	pub expires_in: u64,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct DeviceCode {
+	device_code: String,
+	user_code: String,
+	verification_uri: String,
+	expires_in: u64,
+	interval: u64,
+	message: Option<String>,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct OAuthError {
+	error: String,
+	error_description: String,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct XboxToken {
+	token: String,
+	display_claims: XboxClaims,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct XboxClaims {
+	xui: Vec<XboxUser>,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct XboxUser {
+	uhs: String,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct XboxError {
+	#[serde(rename = "XErr")]
+	xerr: u64,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct MinecraftToken {
+	access_token: String,
+	expires_in: u64,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct Entitlements {
+	items: Vec<Entitlement>,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct Entitlement {
+	name: String,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct Profile {
+	id: String,
+	name: String,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct TokenClaims {
+	xuid: String,
+}
+
+impl Default for Endpoints {
+	fn default() -> Self {
+		Self {
+			client_id: String::from("00000000441cc96b"),
+			scope: String::from("service::user.auth.xboxlive.com::MBI_SSL"),
+			rps_ticket_prefix: String::new(),
+			device_code: String::from("https://login.live.com/oauth20_connect.srf"),
+			token: String::from("https://login.live.com/oauth20_token.srf"),
+			xbox_user: String::from("https://user.auth.xboxlive.com/user/authenticate"),
+			xsts: String::from("https://xsts.auth.xboxlive.com/xsts/authorize"),
+			minecraft_login: String::from(
+				"https://api.minecraftservices.com/authentication/login_with_xbox",
+			),
+			entitlements: String::from("https://api.minecraftservices.com/entitlements/mcstore"),
+			profile: String::from("https://api.minecraftservices.com/minecraft/profile"),
+		}
+	}
+}
+
+impl Endpoints {
+	// Missing fields keep official endpoints
+	pub fn load() -> Result<Self, Error> {
+		let path = Path::new(ENDPOINTS_PATH);
+		if !path.exists() {
+			return Ok(Self::default());
+		}
+
+		Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+	}
+}
+
+pub struct MicrosoftAuth {
+	endpoints: Endpoints,
+	client: Client,
+	poll_unit: Duration,
+}
+
+impl MicrosoftAuth {
+	pub fn new(endpoints: Endpoints) -> Self {
+		Self {
+			endpoints,
+			client: Client::new(),
+			poll_unit: POLL_UNIT,
+		}
+	}
+
+	// Asks user to enter code on microsoft page and waits for it
+	pub fn login_with_device_code(&self) -> Result<MicrosoftToken, Error> {
+		let code: DeviceCode = Self::parse(
+			self.client
+				.post(&self.endpoints.device_code)
+				.form(&[
+					("client_id", self.endpoints.client_id.as_str()),
+					("scope", self.endpoints.scope.as_str()),
+					("response_type", "device_code"),
+				])
+				.send()?,
+			"device code",
+		)?;
+
+		match code.message.as_ref() {
+			Some(message) => println!("{message}"),
+			None => println!(
+				"To sign in, open {} and enter the code {}",
+				code.verification_uri, code.user_code
+			),
+		}
+
+		let mut interval = code.interval.max(1);
+		let mut waited = 0;
+
+		while waited < code.expires_in {
+			thread::sleep(self.poll_unit * interval as u32);
+			waited += interval;
+
+			let response = self
+				.client
+				.post(&self.endpoints.token)
+				.form(&[
+					("client_id", self.endpoints.client_id.as_str()),
+					("grant_type", DEVICE_CODE_GRANT),
+					("device_code", code.device_code.as_str()),
+				])
+				.send()?;
+
+			if response.status().is_success() {
+				return Ok(response.json()?);
+			}
+
+			let error: OAuthError = response.json().unwrap_or_default();
+			match error.error.as_str() {
+				"authorization_pending" => {}
+				"slow_down" => interval += 5,
+				_ => {
+					return Err(Error::Default(format!(
+						"microsoft login failed: {} {}",
+						error.error, error.error_description
+					)))
+				}
+			}
+		}
+
+		Err(Error::Default(String::from(
+			"microsoft login failed: device code expired",
+		)))
+	}
+
+	// Refresh token lives much longer, than access one
+	pub fn refresh(&self, refresh_token: &str) -> Result<MicrosoftToken, Error> {
+		Self::parse(
+			self.client
+				.post(&self.endpoints.token)
+				.form(&[
+					("client_id", self.endpoints.client_id.as_str()),
+					("scope", self.endpoints.scope.as_str()),
+					("grant_type", "refresh_token"),
+					("refresh_token", refresh_token),
+				])
+				.send()?,
+			"microsoft token refresh",
+		)
+	}
+
+	// Everything after microsoft login
+	pub fn get_session(&self, token: &MicrosoftToken) -> Result<AuthSession, Error> {
+		let user: XboxToken = Self::parse(
+			self.client
+				.post(&self.endpoints.xbox_user)
+				.json(&json!({
+					"Properties": {
+						"AuthMethod": "RPS",
+						"SiteName": "user.auth.xboxlive.com",
+						"RpsTicket": format!("{}{}", self.endpoints.rps_ticket_prefix, token.access_token),
+					},
+					"RelyingParty": "http://auth.xboxlive.com",
+					"TokenType": "JWT",
+				}))
+				.send()?,
+			"xbox live authentication",
+		)?;
+
+		let xsts = self.authorize_xsts(&user.token)?;
+		let user_hash = xsts
+			.display_claims
+			.xui
+			.first()
+			.map(|user| user.uhs.clone())
+			.ok_or_else(|| Error::Default(String::from("xsts token has no user hash")))?;
+
+		let minecraft: MinecraftToken = Self::parse(
+			self.client
+				.post(&self.endpoints.minecraft_login)
+				.json(&json!({
+					"identityToken": format!("XBL3.0 x={user_hash};{}", xsts.token),
+				}))
+				.send()?,
+			"minecraft services login",
+		)?;
+
+		let entitlements: Entitlements = Self::parse(
+			self.client
+				.get(&self.endpoints.entitlements)
+				.bearer_auth(&minecraft.access_token)
+				.send()?,
+			"entitlements check",
+		)?;
+		let owns_game = entitlements
+			.items
+			.iter()
+			.any(|item| item.name == "product_minecraft" || item.name == "game_minecraft");
+		if !owns_game {
+			return Err(Error::Default(String::from(
+				"this microsoft account doesn't own minecraft",
+			)));
+		}
+
+		let response = self
+			.client
+			.get(&self.endpoints.profile)
+			.bearer_auth(&minecraft.access_token)
+			.send()?;
+		if response.status().as_u16() == 404 {
+			return Err(Error::Default(String::from(
+				"minecraft profile isn't created yet. Set player name on minecraft.net",
+			)));
+		}
+		let profile: Profile = Self::parse(response, "minecraft profile")?;
+
+		Ok(AuthSession {
+			name: profile.name,
+			uuid: profile.id,
+			xuid: Self::get_xuid(&minecraft.access_token).unwrap_or_default(),
+			access_token: minecraft.access_token,
+			user_type: String::from("msa"),
+			expires_in: minecraft.expires_in,
+			auth_server: None,
+		})
+	}
+
+	fn authorize_xsts(&self, user_token: &str) -> Result<XboxToken, Error> {
+		let response = self
+			.client
+			.post(&self.endpoints.xsts)
+			.json(&json!({
+				"Properties": {
+					"SandboxId": "RETAIL",
+					"UserTokens": [user_token],
+				},
+				"RelyingParty": "rp://api.minecraftservices.com/",
+				"TokenType": "JWT",
+			}))
+			.send()?;
+
+		if response.status().as_u16() == 401 {
+			let error: XboxError = response.json().unwrap_or_default();
+			return Err(Error::Default(String::from(match error.xerr {
+				XERR_NO_XBOX_ACCOUNT => {
+					"this microsoft account has no xbox profile. Create it on xbox.com"
+				}
+				XERR_COUNTRY_BANNED => "xbox live is not available in account's country",
+				XERR_CHILD_ACCOUNT => "child account should be added to a family by an adult",
+				_ => "xsts authorization was denied",
+			})));
+		}
+
+		Self::parse(response, "xsts authorization")
+	}
+
+	// Game token is a JWT, which payload has "xuid"
+	fn get_xuid(access_token: &str) -> Option<String> {
+		let payload = access_token.split('.').nth(1)?;
+		let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+
+		let claims: TokenClaims = serde_json::from_slice(&payload).ok()?;
+		match claims.xuid.is_empty() {
+			true => None,
+			false => Some(claims.xuid),
+		}
+	}
+
+	fn parse<T: DeserializeOwned>(response: Response, step: &str) -> Result<T, Error> {
+		let status = response.status();
+		if !status.is_success() {
+			return Err(Error::Default(format!(
+				"{step} failed ({status}): {}",
+				response.text().unwrap_or_default()
+			)));
+		}
+
+		Ok(response.json()?)
+	}
+}
+
+// Mock server is shared with tests of account store
+#[cfg(test)]
+pub(crate) mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use super::*;
+	use crate::util::testing::{Request, Response, TestServer};
+
+	const XUID: &str = "2535405290271234";
+
+	// Game token is a JWT with xuid in payload
+	pub(crate) fn get_game_token() -> String {
+		format!(
+			"header.{}.signature",
+			URL_SAFE_NO_PAD.encode(json!({ "xuid": XUID }).to_string())
+		)
+	}
+
+	pub(crate) fn get_auth(server: &TestServer) -> MicrosoftAuth {
+		let url = server.get_url();
+		MicrosoftAuth {
+			poll_unit: Duration::from_millis(1),
+			..MicrosoftAuth::new(Endpoints {
+				device_code: format!("{url}/devicecode"),
+				token: format!("{url}/token"),
+				xbox_user: format!("{url}/xbox"),
+				xsts: format!("{url}/xsts"),
+				minecraft_login: format!("{url}/login_with_xbox"),
+				entitlements: format!("{url}/entitlements"),
+				profile: format!("{url}/profile"),
+				..Default::default()
+			})
+		}
+	}
+
+	fn get_header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+		request
+			.headers
+			.iter()
+			.find(|(header, _)| header == name)
+			.map(|(_, value)| value.as_str())
+	}
+
+	// Every step succeeds, unless its path is replaced with custom response
+	pub(crate) fn start_server(
+		custom: impl Fn(&str) -> Option<Response> + Send + 'static,
+	) -> TestServer {
+		let polls = AtomicUsize::new(0);
+
+		TestServer::start(move |request| {
+			if let Some(response) = custom(&request.path) {
+				return response;
+			}
+
+			match request.path.as_str() {
+				"/devicecode" => Response::json(
+					200,
+					json!({
+						"device_code": "device",
+						"user_code": "ABCD-EFGH",
+						"verification_uri": "https://microsoft.com/link",
+						"expires_in": 900,
+						"interval": 1
+					}),
+				),
+				"/token" => match polls.fetch_add(1, Ordering::SeqCst) {
+					0 => Response::json(400, json!({ "error": "authorization_pending" })),
+					1 => Response::json(400, json!({ "error": "slow_down" })),
+					_ => Response::json(
+						200,
+						json!({ "access_token": "ms-access", "refresh_token": "ms-refresh", "expires_in": 3600 }),
+					),
+				},
+				"/xbox" => Response::json(
+					200,
+					json!({ "Token": "xbox-token", "DisplayClaims": { "xui": [{ "uhs": "hash" }] } }),
+				),
+				"/xsts" => Response::json(
+					200,
+					json!({ "Token": "xsts-token", "DisplayClaims": { "xui": [{ "uhs": "hash" }] } }),
+				),
+				"/login_with_xbox" => Response::json(
+					200,
+					json!({ "access_token": get_game_token(), "expires_in": 86400 }),
+				),
+				"/entitlements" => Response::json(
+					200,
+					json!({ "items": [{ "name": "product_minecraft" }, { "name": "game_minecraft" }] }),
+				),
+				"/profile" => Response::json(
+					200,
+					json!({ "id": "069a79f444e94726a5befca90e38aaf5", "name": "Notch" }),
+				),
+				_ => Response::not_found(),
+			}
+		})
+	}
+
+	#[test]
+	fn logs_in_through_whole_chain() {
+		let server = start_server(|_| None);
+		let auth = get_auth(&server);
+
+		let token = auth.login_with_device_code().unwrap();
+		assert_eq!(token.access_token, "ms-access");
+		assert_eq!(token.refresh_token, "ms-refresh");
+
+		let session = auth.get_session(&token).unwrap();
+		assert_eq!(session.name, "Notch");
+		assert_eq!(session.uuid, "069a79f444e94726a5befca90e38aaf5");
+		assert_eq!(session.access_token, get_game_token());
+		assert_eq!(session.xuid, XUID);
+		assert_eq!(session.user_type, "msa");
+		assert_eq!(session.expires_in, 86400);
+
+		let requests = server.get_requests();
+		let paths: Vec<&str> = requests
+			.iter()
+			.map(|request| request.path.as_str())
+			.collect();
+		// Pending and slow_down are waited out
+		assert_eq!(
+			paths,
+			[
+				"/devicecode",
+				"/token",
+				"/token",
+				"/token",
+				"/xbox",
+				"/xsts",
+				"/login_with_xbox",
+				"/entitlements",
+				"/profile"
+			]
+		);
+
+		assert!(requests[1].body.contains("device_code=device"));
+		let xbox: serde_json::Value = serde_json::from_str(&requests[4].body).unwrap();
+		assert_eq!(xbox["Properties"]["RpsTicket"], "ms-access");
+		let xsts: serde_json::Value = serde_json::from_str(&requests[5].body).unwrap();
+		assert_eq!(xsts["Properties"]["UserTokens"][0], "xbox-token");
+		let login: serde_json::Value = serde_json::from_str(&requests[6].body).unwrap();
+		assert_eq!(login["identityToken"], "XBL3.0 x=hash;xsts-token");
+		assert_eq!(
+			get_header(&requests[8], "authorization"),
+			Some(format!("Bearer {}", get_game_token()).as_str())
+		);
+	}
+
+	#[test]
+	fn reports_denied_device_login() {
+		let server = start_server(|path| {
+			(path == "/token").then(|| {
+				Response::json(
+					400,
+					json!({ "error": "access_denied", "error_description": "User declined" }),
+				)
+			})
+		});
+
+		let error = get_auth(&server).login_with_device_code().unwrap_err();
+		assert!(error.to_string().contains("access_denied User declined"));
+	}
+
+	#[test]
+	fn explains_xsts_errors() {
+		let cases = [
+			(XERR_NO_XBOX_ACCOUNT, "has no xbox profile"),
+			(XERR_COUNTRY_BANNED, "not available in account's country"),
+			(XERR_CHILD_ACCOUNT, "added to a family"),
+			(1, "xsts authorization was denied"),
+		];
+
+		for (xerr, message) in cases {
+			let server = start_server(move |path| {
+				(path == "/xsts").then(|| Response::json(401, json!({ "XErr": xerr })))
+			});
+
+			let error = get_auth(&server)
+				.get_session(&MicrosoftToken::default())
+				.unwrap_err();
+			assert!(error.to_string().contains(message), "{error}");
+		}
+	}
+
+	#[test]
+	fn game_should_be_owned() {
+		let server = start_server(|path| {
+			(path == "/entitlements").then(|| Response::json(200, json!({ "items": [] })))
+		});
+
+		let error = get_auth(&server)
+			.get_session(&MicrosoftToken::default())
+			.unwrap_err();
+		assert!(error.to_string().contains("doesn't own minecraft"));
+	}
+
+	#[test]
+	fn profile_should_exist() {
+		let server = start_server(|path| (path == "/profile").then(Response::not_found));
+
+		let error = get_auth(&server)
+			.get_session(&MicrosoftToken::default())
+			.unwrap_err();
+		assert!(error.to_string().contains("profile isn't created yet"));
+	}
+
+	#[test]
+	fn decodes_xuid() {
+		assert_eq!(
+			MicrosoftAuth::get_xuid(&get_game_token()).as_deref(),
+			Some(XUID)
+		);
+		// Padding isn't a part of JWT, but some servers add it
+		let padded = format!(
+			"header.{}==.signature",
+			URL_SAFE_NO_PAD.encode(json!({ "xuid": "1" }).to_string())
+		);
+		assert_eq!(MicrosoftAuth::get_xuid(&padded).as_deref(), Some("1"));
+
+		let without_xuid = format!(
+			"header.{}.signature",
+			URL_SAFE_NO_PAD.encode(json!({ "sub": "1" }).to_string())
+		);
+		assert_eq!(MicrosoftAuth::get_xuid(&without_xuid), None);
+		assert_eq!(MicrosoftAuth::get_xuid("not a jwt"), None);
+	}
+}
@@ -0,0 +1,101 @@
+use std::io::Cursor;
+
+use checksums::{hash_reader, Algorithm};
+
+use crate::util::error::Error;
+
+use super::AuthSession;
+
+/* OFFLINE
+* Server in offline mode (and singleplayer) identifies player by uuid, which is
+* version 3 uuid of "OfflinePlayer:[name]": md5 of it with version and variant bits set
+* Names follow game's rules: 3-16 characters, latin letters, digits and underscore
+*/
+
+pub const DEFAULT_USERNAME: &str = "Player";
+
+const MIN_NAME_LENGTH: usize = 3;
+const MAX_NAME_LENGTH: usize = 16;
+
+impl AuthSession {
+	pub fn offline(name: &str) -> Result<Self, Error> {
+		validate_name(name)?;
+
+		Ok(Self {
+			name: name.to_string(),
+			uuid: get_offline_uuid(name),
+			access_token: String::from("0"),
+			xuid: String::from("0"),
+			user_type: String::from("legacy"),
+			expires_in: 0,
+			auth_server: None,
+		})
+	}
+}
+
+pub fn validate_name(name: &str) -> Result<(), Error> {
+	let valid_length = (MIN_NAME_LENGTH..=MAX_NAME_LENGTH).contains(&name.len());
+	let valid_characters = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+	match valid_length && valid_characters {
+		true => Ok(()),
+		false => Err(Error::Default(format!(
+			"wrong player name: {name}. It should have {MIN_NAME_LENGTH}-{MAX_NAME_LENGTH} latin letters, digits or underscores"
+		))),
+	}
+}
+
+// Without dashes, the same way as uuids of online profiles
+pub fn get_offline_uuid(name: &str) -> String {
+	let hash = hash_reader(
+		&mut Cursor::new(format!("OfflinePlayer:{name}")),
+		Algorithm::MD5,
+	)
+	.to_lowercase();
+
+	let mut bytes: Vec<u8> = (0..hash.len())
+		.step_by(2)
+		.filter_map(|index| u8::from_str_radix(&hash[index..index + 2], 16).ok())
+		.collect();
+
+	// Version 3 (name based, md5)
+	bytes[6] = (bytes[6] & 0x0f) | 0x30;
+	// RFC 4122 variant
+	bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+	bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn uuid_matches_game() {
+		assert_eq!(
+			get_offline_uuid("Notch"),
+			"b50ad385829d3141a2167e7d7539ba7f"
+		);
+		assert_eq!(
+			get_offline_uuid("Steve"),
+			"5627dd98e6be3c21b8a8e92344183641"
+		);
+	}
+
+	#[test]
+	fn name_length_is_limited() {
+		assert!(validate_name("ab").is_err());
+		assert!(validate_name("abc").is_ok());
+		assert!(validate_name(&"a".repeat(16)).is_ok());
+		assert!(validate_name(&"a".repeat(17)).is_err());
+	}
+
+	#[test]
+	fn name_has_only_latin_letters_digits_and_underscores() {
+		assert!(validate_name("Player_1").is_ok());
+		assert!(validate_name("Player-1").is_err());
+		assert!(validate_name("Player 1").is_err());
+		assert!(validate_name("Игрок").is_err());
+		assert!(validate_name("Joué").is_err());
+	}
+}
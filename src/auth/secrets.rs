@@ -0,0 +1,335 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use std::collections::HashMap;
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::error::Error;
+
+/* SECRETS
+* Tokens of accounts are never written as plain text:
+* - Secret Service (GNOME Keyring, KWallet) through "secret-tool", where it's available
+* - otherwise encrypted file data/accounts.secrets with key in data/accounts.key,
+*   which is readable only by user
+* Encrypted file is json of account -> base64 of [nonce 12 bytes][AES-256-GCM encrypted secret],
+* every record has it's own random nonce and is bound to it's account
+* Only requested record is decrypted, so corrupted one doesn't lock out other accounts
+* Key file lies next to secrets, so it only hides them from someone glancing over files,
+* it doesn't protect them: anyone, who can read data directory, can decrypt them.
+* Only Secret Service really protects tokens
+*/
+
+#[cfg(not(test))]
+const SECRET_TOOL: &str = "secret-tool";
+// Tests never touch keyring of user
+#[cfg(test)]
+const SECRET_TOOL: &str = "rostermine-test-no-secret-tool";
+const SECRET_ATTRIBUTE: &str = "rostermine-account";
+
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+
+#[derive(Default, PartialEq, Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretStorage {
+	SecretService,
+	#[default]
+	File,
+}
+
+pub struct SecretFile {
+	path: PathBuf,
+	key_path: PathBuf,
+}
+
+impl SecretStorage {
+	// Secret Service is preferred, if it works
+	pub fn store(key: &str, secret: &str, file: &SecretFile) -> Result<Self, Error> {
+		if Self::store_in_service(key, secret) {
+			// Older copy shouldn't stay in file
+			file.remove(key)?;
+			return Ok(Self::SecretService);
+		}
+
+		file.store(key, secret)?;
+		Ok(Self::File)
+	}
+
+	pub fn lookup(&self, key: &str, file: &SecretFile) -> Result<String, Error> {
+		match self {
+			Self::SecretService => {
+				let output = Command::new(SECRET_TOOL)
+					.args(["lookup", SECRET_ATTRIBUTE, key])
+					.stderr(Stdio::null())
+					.output()?;
+
+				match output.status.success() {
+					true => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+					false => Err(Error::Default(format!(
+						"secret of account {key} is not found in secret service"
+					))),
+				}
+			}
+			Self::File => file.lookup(key),
+		}
+	}
+
+	pub fn remove(&self, key: &str, file: &SecretFile) -> Result<(), Error> {
+		match self {
+			Self::SecretService => {
+				Command::new(SECRET_TOOL)
+					.args(["clear", SECRET_ATTRIBUTE, key])
+					.stderr(Stdio::null())
+					.status()?;
+				Ok(())
+			}
+			Self::File => file.remove(key),
+		}
+	}
+
+	fn store_in_service(key: &str, secret: &str) -> bool {
+		let child = Command::new(SECRET_TOOL)
+			.args([
+				"store",
+				&format!("--label=rostermine account {key}"),
+				SECRET_ATTRIBUTE,
+				key,
+			])
+			.stdin(Stdio::piped())
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.spawn();
+		// Not installed
+		let Ok(mut child) = child else {
+			return false;
+		};
+
+		let written = child
+			.stdin
+			.take()
+			.is_some_and(|mut stdin| stdin.write_all(secret.as_bytes()).is_ok());
+
+		child.wait().is_ok_and(|status| status.success()) && written
+	}
+}
+
+impl SecretFile {
+	pub fn new(data_dir: &str) -> Self {
+		Self {
+			path: Path::new(data_dir).join("accounts.secrets"),
+			key_path: Path::new(data_dir).join("accounts.key"),
+		}
+	}
+
+	fn store(&self, key: &str, secret: &str) -> Result<(), Error> {
+		let mut records = self.read()?;
+		records.insert(
+			key.to_string(),
+			Self::encrypt(&self.get_cipher()?, key, secret)?,
+		);
+		self.write(&records)
+	}
+
+	fn lookup(&self, key: &str) -> Result<String, Error> {
+		let record = self
+			.read()?
+			.remove(key)
+			.ok_or_else(|| Error::Default(format!("secret of account {key} is not found")))?;
+
+		Self::decrypt(&self.get_cipher()?, key, &record)
+	}
+
+	fn remove(&self, key: &str) -> Result<(), Error> {
+		let mut records = self.read()?;
+		if records.remove(key).is_some() {
+			self.write(&records)?;
+		}
+		Ok(())
+	}
+
+	// Encrypted records by account
+	fn read(&self) -> Result<HashMap<String, String>, Error> {
+		if !self.path.exists() {
+			return Ok(Default::default());
+		}
+
+		Ok(serde_json::from_slice(&fs::read(&self.path)?)?)
+	}
+
+	fn write(&self, records: &HashMap<String, String>) -> Result<(), Error> {
+		Self::write_private(&self.path, &serde_json::to_vec(records)?)
+	}
+
+	// Account is authenticated along with secret, so records can't be swapped
+	fn encrypt(cipher: &Aes256Gcm, key: &str, secret: &str) -> Result<String, Error> {
+		let mut nonce = [0u8; NONCE_SIZE];
+		Self::fill_random(&mut nonce)?;
+
+		let payload = Payload {
+			msg: secret.as_bytes(),
+			aad: key.as_bytes(),
+		};
+		let encrypted = cipher
+			.encrypt(Nonce::from_slice(&nonce), payload)
+			.map_err(|_| Error::Default(format!("failed to encrypt secret of account {key}")))?;
+
+		Ok(STANDARD.encode([nonce.as_slice(), &encrypted].concat()))
+	}
+
+	fn decrypt(cipher: &Aes256Gcm, key: &str, record: &str) -> Result<String, Error> {
+		let corrupted = || {
+			Error::Default(format!(
+				"secret of account {key} is corrupted or encrypted with other key"
+			))
+		};
+
+		let record = STANDARD.decode(record).map_err(|_| corrupted())?;
+		if record.len() < NONCE_SIZE {
+			return Err(corrupted());
+		}
+		let (nonce, encrypted) = record.split_at(NONCE_SIZE);
+
+		let payload = Payload {
+			msg: encrypted,
+			aad: key.as_bytes(),
+		};
+		let secret = cipher
+			.decrypt(Nonce::from_slice(nonce), payload)
+			.map_err(|_| corrupted())?;
+
+		String::from_utf8(secret).map_err(|_| corrupted())
+	}
+
+	// Key is created on first use
+	fn get_cipher(&self) -> Result<Aes256Gcm, Error> {
+		if !self.key_path.exists() {
+			let mut key = [0u8; KEY_SIZE];
+			Self::fill_random(&mut key)?;
+			Self::write_private(&self.key_path, &key)?;
+		}
+
+		Aes256Gcm::new_from_slice(&fs::read(&self.key_path)?)
+			.map_err(|_| Error::Default(format!("wrong key file {}", self.key_path.display())))
+	}
+
+	fn fill_random(buffer: &mut [u8]) -> Result<(), Error> {
+		getrandom::fill(buffer).map_err(|e| Error::Default(format!("random generator: {e}")))
+	}
+
+	#[cfg(unix)]
+	fn write_private(path: &Path, data: &[u8]) -> Result<(), Error> {
+		use std::os::unix::fs::OpenOptionsExt;
+
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::OpenOptions::new()
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.mode(0o600)
+			.open(path)?
+			.write_all(data)?;
+		Ok(())
+	}
+	#[cfg(not(unix))]
+	fn write_private(path: &Path, data: &[u8]) -> Result<(), Error> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::write(path, data)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::util::testing::get_temp_dir;
+
+	fn get_file(name: &str) -> SecretFile {
+		SecretFile::new(get_temp_dir(name).to_str().unwrap())
+	}
+
+	fn read_records(file: &SecretFile) -> HashMap<String, String> {
+		serde_json::from_slice(&fs::read(&file.path).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn stores_and_removes_secrets() {
+		let file = get_file("secrets-store");
+		file.store("first", "token-1").unwrap();
+		file.store("second", "token-2").unwrap();
+
+		assert_eq!(file.lookup("first").unwrap(), "token-1");
+		assert!(!fs::read_to_string(&file.path).unwrap().contains("token-1"));
+
+		file.remove("first").unwrap();
+		assert!(file.lookup("first").is_err());
+		assert_eq!(file.lookup("second").unwrap(), "token-2");
+	}
+
+	#[test]
+	fn every_write_uses_new_nonce() {
+		let file = get_file("secrets-nonce");
+		file.store("account", "token").unwrap();
+		let first = read_records(&file).remove("account").unwrap();
+
+		file.store("account", "token").unwrap();
+		let second = read_records(&file).remove("account").unwrap();
+
+		assert_ne!(first, second);
+	}
+
+	#[test]
+	fn swapped_records_are_rejected() {
+		let file = get_file("secrets-swap");
+		file.store("first", "token-1").unwrap();
+		file.store("second", "token-2").unwrap();
+
+		let mut records = read_records(&file);
+		let first = records["first"].clone();
+		records.insert(String::from("second"), first);
+		fs::write(&file.path, serde_json::to_vec(&records).unwrap()).unwrap();
+
+		// Record of "first" is valid, but not for other account
+		assert_eq!(file.lookup("first").unwrap(), "token-1");
+		assert!(file.lookup("second").is_err());
+	}
+
+	#[test]
+	fn corrupted_record_affects_only_its_account() {
+		let file = get_file("secrets-corrupted");
+		file.store("first", "token-1").unwrap();
+		file.store("second", "token-2").unwrap();
+
+		let mut records = read_records(&file);
+		records.insert(String::from("first"), String::from("garbage"));
+		fs::write(&file.path, serde_json::to_vec(&records).unwrap()).unwrap();
+
+		assert!(file.lookup("first").is_err());
+		assert_eq!(file.lookup("second").unwrap(), "token-2");
+
+		// Account is able to sign in again
+		file.store("first", "token-3").unwrap();
+		assert_eq!(file.lookup("first").unwrap(), "token-3");
+		assert_eq!(file.lookup("second").unwrap(), "token-2");
+	}
+
+	#[test]
+	fn other_key_is_rejected() {
+		let file = get_file("secrets-key");
+		file.store("account", "token").unwrap();
+
+		fs::write(&file.key_path, [0u8; KEY_SIZE]).unwrap();
+		assert!(file.lookup("account").is_err());
+	}
+}
@@ -0,0 +1,491 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use checksums::{hash_file, Algorithm};
+
+use reqwest::blocking::{Client, Response};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::fetch::minecraft::Minecraft;
+use crate::fetch::textfile::RetrievePlainText;
+use crate::fetch::vanilla::DataObject;
+use crate::util::error::Error;
+
+use super::AuthSession;
+
+/* YGGDRASIL
+* Third-party auth servers implement Mojang's legacy Yggdrasil API under own API root:
+* - [root]/authserver/authenticate: login and password -> access and client tokens, profiles
+* - [root]/authserver/refresh: old access token -> new one (and profile selection)
+* - [root]/authserver/validate: 204 for token, that still works
+* - [root]/sessionserver/session/minecraft/profile/[uuid]: player name
+* Game itself knows only Mojang servers, so authlib-injector agent redirects it:
+* -javaagent:[authlib-injector.jar]=[root]
+* Server may point to real API root with "X-Authlib-Injector-API-Location" header
+*/
+
+const API_LOCATION_HEADER: &str = "X-Authlib-Injector-API-Location";
+
+// Release info of authlib-injector
+pub const INJECTOR_ARTIFACT_URL: &str = "https://authlib-injector.yushi.moe/artifact/latest.json";
+const INJECTOR_GROUP_PATH: &str = "moe/yushi/authlibinjector/authlib-injector";
+// Unreachable server shouldn't hold the launch
+const METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct YggdrasilAuth {
+	api_root: String,
+	client: Client,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct TokenResponse {
+	access_token: String,
+	client_token: String,
+	available_profiles: Vec<Profile>,
+	selected_profile: Option<Profile>,
+}
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Profile {
+	pub id: String,
+	pub name: String,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct YggdrasilError {
+	error: String,
+	error_message: String,
+}
+
+// Session is missing, when user has several players and server hasn't picked one
+pub struct YggdrasilLogin {
+	pub session: Option<AuthSession>,
+	pub access_token: String,
+	pub client_token: String,
+	pub available_profiles: Vec<Profile>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct InjectorArtifact {
+	version: String,
+	download_url: String,
+	checksums: InjectorChecksums,
+}
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct InjectorChecksums {
+	sha256: String,
+}
+pub struct AuthlibInjector {}
+
+impl YggdrasilAuth {
+	pub fn new(api_root: &str) -> Self {
+		Self {
+			api_root: api_root.trim_end_matches('/').to_string(),
+			client: Client::new(),
+		}
+	}
+
+	// Users usually know only address of site, not the API root
+	pub fn resolve(url: &str) -> Result<Self, Error> {
+		let url = match url.contains("://") {
+			true => url.to_string(),
+			false => format!("https://{url}"),
+		};
+
+		let response = Client::new().get(&url).send()?;
+		let location = response
+			.headers()
+			.get(API_LOCATION_HEADER)
+			.and_then(|value| value.to_str().ok());
+
+		Ok(Self::new(&match location {
+			Some(location) if location.contains("://") => location.to_string(),
+			Some(location) => response.url().join(location).map_or(url, String::from),
+			None => url,
+		}))
+	}
+
+	pub fn get_api_root(&self) -> &str {
+		&self.api_root
+	}
+
+	pub fn authenticate(&self, login: &str, password: &str) -> Result<YggdrasilLogin, Error> {
+		let token: TokenResponse = Self::parse(
+			self.client
+				.post(format!("{}/authserver/authenticate", self.api_root))
+				.json(&json!({
+					"agent": { "name": "Minecraft", "version": 1 },
+					"username": login,
+					"password": password,
+					"requestUser": false,
+				}))
+				.send()?,
+			"yggdrasil authentication",
+		)?;
+
+		Ok(YggdrasilLogin {
+			session: token
+				.selected_profile
+				.as_ref()
+				.map(|profile| self.get_session(profile, &token.access_token)),
+			access_token: token.access_token,
+			client_token: token.client_token,
+			available_profiles: token.available_profiles,
+		})
+	}
+
+	// Profile is selected only once, the first refresh after authentication
+	pub fn refresh(
+		&self,
+		access_token: &str,
+		client_token: &str,
+		profile: Option<&Profile>,
+	) -> Result<AuthSession, Error> {
+		let mut request = json!({
+			"accessToken": access_token,
+			"clientToken": client_token,
+			"requestUser": false,
+		});
+		if let Some(profile) = profile {
+			request["selectedProfile"] = json!(profile);
+		}
+
+		let token: TokenResponse = Self::parse(
+			self.client
+				.post(format!("{}/authserver/refresh", self.api_root))
+				.json(&request)
+				.send()?,
+			"yggdrasil token refresh",
+		)?;
+
+		let profile = token
+			.selected_profile
+			.or_else(|| profile.cloned())
+			.ok_or_else(|| Error::Default(String::from("auth server selected no profile")))?;
+
+		Ok(self.get_session(&profile, &token.access_token))
+	}
+
+	pub fn validate(&self, access_token: &str, client_token: &str) -> Result<bool, Error> {
+		let response = self
+			.client
+			.post(format!("{}/authserver/validate", self.api_root))
+			.json(&json!({
+				"accessToken": access_token,
+				"clientToken": client_token,
+			}))
+			.send()?;
+
+		Ok(response.status().is_success())
+	}
+
+	// Player name may be changed on the site
+	pub fn get_profile(&self, uuid: &str) -> Result<Profile, Error> {
+		Self::parse(
+			self.client
+				.get(format!(
+					"{}/sessionserver/session/minecraft/profile/{uuid}",
+					self.api_root
+				))
+				.send()?,
+			"yggdrasil profile",
+		)
+	}
+
+	pub fn get_session(&self, profile: &Profile, access_token: &str) -> AuthSession {
+		AuthSession {
+			name: profile.name.clone(),
+			uuid: profile.id.clone(),
+			access_token: access_token.to_string(),
+			xuid: String::from("0"),
+			user_type: String::from("mojang"),
+			expires_in: 0,
+			auth_server: Some(self.api_root.clone()),
+		}
+	}
+
+	fn parse<T: DeserializeOwned>(response: Response, step: &str) -> Result<T, Error> {
+		let status = response.status();
+		if !status.is_success() {
+			let error: YggdrasilError = response.json().unwrap_or_default();
+			return Err(Error::Default(format!(
+				"{step} failed ({status}): {} {}",
+				error.error, error.error_message
+			)));
+		}
+
+		Ok(response.json()?)
+	}
+}
+
+impl AuthlibInjector {
+	// Latest release is stored among libraries, known one is used without network
+	pub fn install(libraries_dir: &str, artifact_url: &str) -> Result<PathBuf, Error> {
+		let text = Self::retrieve_text(
+			&format!("{libraries_dir}/{INJECTOR_GROUP_PATH}/latest.json"),
+			&artifact_url.to_string(),
+			None,
+		)?;
+		let artifact: InjectorArtifact = serde_json::from_str(&text)?;
+
+		let path = format!(
+			"{libraries_dir}/{INJECTOR_GROUP_PATH}/{0}/authlib-injector-{0}.jar",
+			artifact.version
+		);
+		let is_valid = |path: &str| {
+			Path::new(path).exists()
+				&& hash_file(Path::new(path), Algorithm::SHA2256)
+					.eq_ignore_ascii_case(&artifact.checksums.sha256)
+		};
+
+		if !is_valid(&path) {
+			// Only sha256 is published, so it's checked here
+			if Path::new(&path).exists() {
+				fs::remove_file(&path)?;
+			}
+			Minecraft::download_objects(&[DataObject {
+				path: path.clone(),
+				url: artifact.download_url,
+				..Default::default()
+			}])?;
+
+			if !is_valid(&path) {
+				fs::remove_file(&path)?;
+				return Err(Error::Default(format!(
+					"hash mismatch for authlib-injector {}",
+					artifact.version
+				)));
+			}
+		}
+
+		Ok(PathBuf::from(path))
+	}
+
+	// Prefetched metadata saves game one request, it isn't required
+	pub fn get_jvm_arguments(jar: &Path, api_root: &str) -> Vec<String> {
+		let mut arguments = vec![format!("-javaagent:{}={api_root}", jar.display())];
+
+		let metadata = Client::new()
+			.get(api_root)
+			.timeout(METADATA_TIMEOUT)
+			.send()
+			.and_then(|response| response.error_for_status())
+			.and_then(|response| response.text());
+		if let Ok(metadata) = metadata {
+			arguments.push(format!(
+				"-Dauthlibinjector.yggdrasil.prefetched={}",
+				STANDARD.encode(metadata)
+			));
+		}
+
+		arguments
+	}
+}
+impl RetrievePlainText for AuthlibInjector {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::util::testing::{Response, TestServer};
+
+	fn profile(id: &str, name: &str) -> serde_json::Value {
+		json!({ "id": id, "name": name })
+	}
+
+	fn get_body(server: &TestServer, path: &str) -> serde_json::Value {
+		let request = server
+			.get_requests()
+			.into_iter()
+			.find(|request| request.path == path)
+			.unwrap();
+		serde_json::from_str(&request.body).unwrap()
+	}
+
+	#[test]
+	fn resolves_api_location() {
+		let server = TestServer::start(|request| match request.path.as_str() {
+			"/" => Response::new(200, "").with_header(API_LOCATION_HEADER, "/api/yggdrasil/"),
+			_ => Response::not_found(),
+		});
+
+		let auth = YggdrasilAuth::resolve(server.get_url()).unwrap();
+		assert_eq!(
+			auth.get_api_root(),
+			format!("{}/api/yggdrasil", server.get_url())
+		);
+	}
+
+	#[test]
+	fn keeps_url_without_api_location() {
+		let server = TestServer::start(|_| Response::new(200, ""));
+
+		let auth = YggdrasilAuth::resolve(server.get_url()).unwrap();
+		assert_eq!(auth.get_api_root(), server.get_url());
+	}
+
+	#[test]
+	fn authenticates_with_single_profile() {
+		let server = TestServer::start(|request| match request.path.as_str() {
+			"/authserver/authenticate" => Response::json(
+				200,
+				json!({
+					"accessToken": "access",
+					"clientToken": "client",
+					"availableProfiles": [profile("1", "Steve")],
+					"selectedProfile": profile("1", "Steve"),
+				}),
+			),
+			_ => Response::not_found(),
+		});
+
+		let login = YggdrasilAuth::new(server.get_url())
+			.authenticate("steve@example.com", "secret")
+			.unwrap();
+		let session = login.session.unwrap();
+		assert_eq!(session.name, "Steve");
+		assert_eq!(session.access_token, "access");
+		assert_eq!(session.auth_server.as_deref(), Some(server.get_url()));
+		assert_eq!(login.client_token, "client");
+
+		let body = get_body(&server, "/authserver/authenticate");
+		assert_eq!(body["username"], "steve@example.com");
+		assert_eq!(body["agent"]["name"], "Minecraft");
+	}
+
+	#[test]
+	fn refresh_selects_one_of_profiles() {
+		let server = TestServer::start(|request| match request.path.as_str() {
+			"/authserver/authenticate" => Response::json(
+				200,
+				json!({
+					"accessToken": "access",
+					"clientToken": "client",
+					"availableProfiles": [profile("1", "Steve"), profile("2", "Alex")],
+				}),
+			),
+			"/authserver/refresh" => Response::json(
+				200,
+				json!({
+					"accessToken": "refreshed",
+					"clientToken": "client",
+					"selectedProfile": profile("2", "Alex"),
+				}),
+			),
+			_ => Response::not_found(),
+		});
+		let auth = YggdrasilAuth::new(server.get_url());
+
+		let login = auth.authenticate("player", "secret").unwrap();
+		assert!(login.session.is_none());
+		assert_eq!(login.available_profiles.len(), 2);
+
+		let session = auth
+			.refresh(
+				&login.access_token,
+				&login.client_token,
+				Some(&login.available_profiles[1]),
+			)
+			.unwrap();
+		assert_eq!(session.name, "Alex");
+		assert_eq!(session.uuid, "2");
+		assert_eq!(session.access_token, "refreshed");
+
+		let body = get_body(&server, "/authserver/refresh");
+		assert_eq!(body["accessToken"], "access");
+		assert_eq!(body["selectedProfile"], profile("2", "Alex"));
+	}
+
+	#[test]
+	fn refresh_without_profile_sends_no_selection() {
+		let server = TestServer::start(|_| {
+			Response::json(
+				200,
+				json!({ "accessToken": "refreshed", "selectedProfile": profile("1", "Steve") }),
+			)
+		});
+
+		let session = YggdrasilAuth::new(server.get_url())
+			.refresh("access", "client", None)
+			.unwrap();
+		assert_eq!(session.name, "Steve");
+		assert!(get_body(&server, "/authserver/refresh")
+			.get("selectedProfile")
+			.is_none());
+	}
+
+	#[test]
+	fn reports_server_error() {
+		let server = TestServer::start(|_| {
+			Response::json(
+				403,
+				json!({
+					"error": "ForbiddenOperationException",
+					"errorMessage": "Invalid credentials.",
+				}),
+			)
+		});
+
+		let error = YggdrasilAuth::new(server.get_url())
+			.authenticate("player", "wrong")
+			.err()
+			.unwrap()
+			.to_string();
+		assert!(error.contains("403"));
+		assert!(error.contains("ForbiddenOperationException"));
+		assert!(error.contains("Invalid credentials."));
+	}
+
+	#[test]
+	fn validates_token() {
+		let server = TestServer::start(|request| match request.body.contains("\"valid\"") {
+			true => Response::new(204, ""),
+			false => Response::json(403, json!({ "error": "ForbiddenOperationException" })),
+		});
+		let auth = YggdrasilAuth::new(server.get_url());
+
+		assert!(auth.validate("valid", "client").unwrap());
+		assert!(!auth.validate("expired", "client").unwrap());
+	}
+
+	#[test]
+	fn gets_profile() {
+		let server = TestServer::start(|request| match request.path.as_str() {
+			"/sessionserver/session/minecraft/profile/1" => {
+				Response::json(200, profile("1", "Renamed"))
+			}
+			_ => Response::not_found(),
+		});
+		let auth = YggdrasilAuth::new(server.get_url());
+
+		assert_eq!(auth.get_profile("1").unwrap().name, "Renamed");
+		assert!(auth.get_profile("2").is_err());
+	}
+
+	#[test]
+	fn prefetches_metadata() {
+		let server = TestServer::start(|_| Response::json(200, json!({ "meta": {} })));
+
+		let arguments =
+			AuthlibInjector::get_jvm_arguments(Path::new("agent.jar"), server.get_url());
+		assert_eq!(
+			arguments,
+			[
+				format!("-javaagent:agent.jar={}", server.get_url()),
+				format!(
+					"-Dauthlibinjector.yggdrasil.prefetched={}",
+					STANDARD.encode(r#"{"meta":{}}"#)
+				),
+			]
+		);
+	}
+}
@@ -0,0 +1,643 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::util::error::Error;
+
+/* CRASH REPORTS
+* After crash the newest report, written since launch, is looked for in instance:
+* - crash-reports/crash-*.txt: game's own report with description and stack trace
+* - hs_err_pid*.log: JVM report of native crash (drivers, memory)
+* Launch log is analysed, when game died without report
+* Report text is matched against known causes, like wrong java or missing mod dependency
+*/
+
+const GAME_REPORTS_DIR: &str = "crash-reports";
+const JVM_REPORT_PREFIX: &str = "hs_err_pid";
+
+// Stack trace is shown shortened, full one is in report
+const SHOWN_STACK_LINES: usize = 6;
+const SHOWN_DETAIL_LINES: usize = 5;
+
+// Class file versions start from 45 (java 1.1)
+const CLASS_VERSION_OFFSET: u32 = 44;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ReportKind {
+	Game,
+	Jvm,
+	Log,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Cause {
+	WrongJava,
+	JavaTooNew,
+	MissingDependency,
+	OutOfMemory,
+	SystemOutOfMemory,
+	GraphicsDriver,
+}
+
+#[derive(Debug)]
+pub struct CrashReport {
+	pub path: PathBuf,
+	pub kind: ReportKind,
+	pub time: Option<String>,
+	pub description: Option<String>,
+	pub exception: Option<String>,
+	pub stack: Vec<String>,
+	text: String,
+}
+
+impl Cause {
+	const ALL: [Self; 6] = [
+		Self::WrongJava,
+		Self::JavaTooNew,
+		Self::MissingDependency,
+		Self::OutOfMemory,
+		Self::SystemOutOfMemory,
+		Self::GraphicsDriver,
+	];
+
+	fn get_patterns(&self) -> &'static [&'static str] {
+		match self {
+			Self::WrongJava => &[
+				"UnsupportedClassVersionError",
+				"compiled by a more recent version of the Java Runtime",
+				"of 'Java' (java)",
+				// Old versions on java 9+
+				"cannot be cast to class java.net.URLClassLoader",
+			],
+			Self::JavaTooNew => &["Unsupported class file major version"],
+			Self::MissingDependency => &[
+				"which is missing",
+				"Mod resolution encountered an incompatible mod set",
+				"ModResolutionException",
+				"Missing or unsupported mandatory dependencies",
+				"Could not find required mod",
+			],
+			Self::OutOfMemory => &["java.lang.OutOfMemoryError"],
+			Self::SystemOutOfMemory => &[
+				"There is insufficient memory for the Java Runtime Environment",
+				"Could not reserve enough space for object heap",
+			],
+			Self::GraphicsDriver => &[
+				"Pixel format not accelerated",
+				"GLFW error 65542",
+				"GLFW error 65543",
+				"The driver does not appear to support OpenGL",
+				"Failed to create OpenGL context",
+				"No OpenGL context",
+			],
+		}
+	}
+
+	// Libraries of crashed native code. They are matched only against problematic and native frames,
+	// because JVM report lists every loaded library, drivers included
+	fn get_frame_patterns(&self) -> &'static [&'static str] {
+		match self {
+			Self::GraphicsDriver => &[
+				"[libGL",
+				"[libnvidia-glcore",
+				"_dri.so",
+				"[nvoglv",
+				"[atio6axx",
+				"[ig7icd",
+				"[ig9icd",
+				"[ig75icd",
+			],
+			_ => &[],
+		}
+	}
+
+	fn describe(&self, text: &str) -> String {
+		match self {
+			Self::WrongJava => match get_required_java(text) {
+				Some(version) => format!(
+					"game or mod needs java {version}. Set \"javaVersion\": {version} in instance.json or pass -j"
+				),
+				None if text.contains("java.net.URLClassLoader") => String::from(
+					"this version needs java 8. Set \"javaVersion\": 8 in instance.json or pass -j",
+				),
+				None => String::from(
+					"game or mod needs other java. Set \"javaVersion\" in instance.json or pass -j",
+				),
+			},
+			Self::JavaTooNew => String::from(
+				"java is too new for modloader. Remove \"javaVersion\" from instance.json to use the one, that version requires",
+			),
+			Self::MissingDependency => {
+				let mut description = String::from("mod dependency is missing or has wrong version");
+				for line in get_lines_with(text, &["requires", "Mod ID:", "which is missing"])
+					.take(SHOWN_DETAIL_LINES)
+				{
+					description.push_str(&format!("\n    {line}"));
+				}
+				description
+			}
+			Self::OutOfMemory => String::from(
+				"game is out of heap memory. Give it more with -m (like \"-m 6G\" or \"-m auto\")",
+			),
+			Self::SystemOutOfMemory => String::from(
+				"system has not enough memory for requested heap. Lower -m or close other programs",
+			),
+			Self::GraphicsDriver => String::from(
+				"graphics driver failed. Update it and check, that game runs on GPU with OpenGL 3.2 or newer",
+			),
+		}
+	}
+}
+
+impl CrashReport {
+	// Reports of previous launches are skipped
+	pub fn find_newest(instance_dir: &str, since: SystemTime) -> Result<Option<Self>, Error> {
+		let instance = Path::new(instance_dir);
+
+		let mut candidates = Self::list_files(&instance.join(GAME_REPORTS_DIR), |name| {
+			name.starts_with("crash-") && name.ends_with(".txt")
+		})?;
+		candidates.extend(Self::list_files(instance, |name| {
+			name.starts_with(JVM_REPORT_PREFIX) && name.ends_with(".log")
+		})?);
+
+		let newest = candidates
+			.into_iter()
+			.filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+			.filter(|(modified, _)| *modified >= since)
+			.max_by_key(|(modified, _)| *modified);
+
+		match newest {
+			Some((_, path)) => Ok(Some(Self::parse(&path)?)),
+			None => Ok(None),
+		}
+	}
+
+	pub fn parse(path: &Path) -> Result<Self, Error> {
+		let name = path
+			.file_name()
+			.map(|name| name.to_string_lossy().into_owned())
+			.unwrap_or_default();
+
+		let mut report = Self {
+			path: path.to_path_buf(),
+			kind: match name {
+				_ if name.starts_with(JVM_REPORT_PREFIX) => ReportKind::Jvm,
+				_ if name.starts_with("crash-") => ReportKind::Game,
+				_ => ReportKind::Log,
+			},
+			time: None,
+			description: None,
+			exception: None,
+			stack: Default::default(),
+			// Logs may contain anything
+			text: String::from_utf8_lossy(&fs::read(path)?).into_owned(),
+		};
+
+		match report.kind {
+			ReportKind::Game => report.parse_game(),
+			ReportKind::Jvm => report.parse_jvm(),
+			ReportKind::Log => report.parse_log(),
+		}
+
+		Ok(report)
+	}
+
+	pub fn diagnose(&self) -> Vec<String> {
+		self.get_causes()
+			.iter()
+			.map(|cause| cause.describe(&self.text))
+			.collect()
+	}
+
+	fn get_causes(&self) -> Vec<Cause> {
+		let frames: Vec<&String> = self.exception.iter().chain(&self.stack).collect();
+
+		Cause::ALL
+			.into_iter()
+			.filter(|cause| {
+				cause
+					.get_patterns()
+					.iter()
+					.any(|pattern| self.text.contains(pattern))
+					|| cause
+						.get_frame_patterns()
+						.iter()
+						.any(|pattern| frames.iter().any(|frame| frame.contains(pattern)))
+			})
+			.collect()
+	}
+
+	pub fn print_summary(&self) {
+		let name = match self.kind {
+			ReportKind::Game => "Crash report",
+			ReportKind::Jvm => "JVM crash report",
+			ReportKind::Log => "Game log",
+		};
+		println!("\n{name}: {}", self.path.display());
+
+		if let Some(time) = self.time.as_ref() {
+			println!("Time: {time}");
+		}
+		if let Some(description) = self.description.as_ref() {
+			println!("Description: {description}");
+		}
+		if let Some(exception) = self.exception.as_ref() {
+			println!("{exception}");
+		}
+		for line in self.stack.iter().take(SHOWN_STACK_LINES) {
+			println!("\t{line}");
+		}
+		if self.stack.len() > SHOWN_STACK_LINES {
+			println!("\t... {} more", self.stack.len() - SHOWN_STACK_LINES);
+		}
+
+		let causes = self.diagnose();
+		if causes.is_empty() {
+			println!("Cause is unknown, see the full report");
+			return;
+		}
+		println!("Diagnosis:");
+		for description in causes {
+			println!("- {description}");
+		}
+	}
+
+	// ---- Minecraft Crash Report ----
+	// // comment
+	//
+	// Time: ...
+	// Description: ...
+	//
+	// java.lang.Exception: ...
+	//     at ...
+	fn parse_game(&mut self) {
+		let mut lines = self.text.lines();
+
+		for line in lines.by_ref() {
+			if let Some(time) = line.strip_prefix("Time: ") {
+				self.time = Some(time.trim().to_string());
+			}
+			if let Some(description) = line.strip_prefix("Description: ") {
+				self.description = Some(description.trim().to_string());
+				break;
+			}
+		}
+
+		self.exception = lines
+			.by_ref()
+			.find(|line| !line.trim().is_empty())
+			.map(|line| line.trim().to_string());
+		self.stack = Self::read_stack(lines);
+	}
+
+	// # A fatal error has been detected by the Java Runtime Environment:
+	// #
+	// #  SIGSEGV (0xb) at pc=..., pid=..., tid=...
+	// ...
+	// # Problematic frame:
+	// # C  [libGL.so.1+0x...]
+	// ...
+	// Native frames: (J=compiled Java code, j=interpreted, Vv=VM code, C=native code)
+	// C  [...]
+	fn parse_jvm(&mut self) {
+		let comments: Vec<&str> = self
+			.text
+			.lines()
+			.filter_map(|line| line.strip_prefix('#'))
+			.map(str::trim)
+			.filter(|line| !line.is_empty())
+			.collect();
+
+		self.description = comments
+			.iter()
+			.position(|line| line.starts_with("A fatal error has been detected"))
+			.and_then(|index| comments.get(index + 1))
+			.or_else(|| comments.first())
+			.map(|line| line.to_string());
+		self.exception = comments
+			.iter()
+			.position(|line| line.starts_with("Problematic frame:"))
+			.and_then(|index| comments.get(index + 1))
+			.map(|line| line.to_string());
+
+		self.stack = self
+			.text
+			.lines()
+			.skip_while(|line| !line.starts_with("Native frames:"))
+			.skip(1)
+			.take_while(|line| !line.trim().is_empty())
+			.map(|line| line.trim().to_string())
+			.collect();
+	}
+
+	// Log has no header, first thrown exception is the most useful one
+	fn parse_log(&mut self) {
+		let mut lines = self.text.lines();
+
+		self.exception = lines
+			.by_ref()
+			.find(|line| is_exception_line(line))
+			.map(|line| line.trim().to_string());
+		self.stack = Self::read_stack(lines);
+	}
+
+	fn read_stack<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<String> {
+		lines
+			.map(str::trim)
+			.take_while(|line| {
+				line.starts_with("at ") || line.starts_with("Caused by:") || line.starts_with("...")
+			})
+			.map(String::from)
+			.collect()
+	}
+
+	fn list_files(dir: &Path, filter: impl Fn(&str) -> bool) -> Result<Vec<PathBuf>, Error> {
+		if !dir.is_dir() {
+			return Ok(Default::default());
+		}
+
+		Ok(fs::read_dir(dir)?
+			.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| {
+				path.file_name()
+					.and_then(|name| name.to_str())
+					.is_some_and(&filter)
+			})
+			.collect())
+	}
+}
+
+// "java.lang.IllegalStateException: message" or just class name
+fn is_exception_line(line: &str) -> bool {
+	let class = line.trim().split(':').next().unwrap_or_default();
+
+	class.contains('.')
+		&& !class.contains(' ')
+		&& (class.ends_with("Exception") || class.ends_with("Error"))
+}
+
+// "class file version 65.0" -> 21
+fn get_required_java(text: &str) -> Option<u32> {
+	let (_, rest) = text.split_once("class file version ")?;
+	let major: u32 = rest.split('.').next()?.parse().ok()?;
+
+	major.checked_sub(CLASS_VERSION_OFFSET)
+}
+
+fn get_lines_with<'a>(text: &'a str, patterns: &'a [&str]) -> impl Iterator<Item = &'a str> {
+	text.lines()
+		.map(str::trim)
+		.filter(|line| patterns.iter().any(|pattern| line.contains(pattern)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::util::testing::get_temp_dir;
+
+	const GAME_REPORT: &str = "---- Minecraft Crash Report ----
+// Who set us up the TNT?
+
+Time: 2024-03-01 12:00:00
+Description: Initializing game
+
+java.lang.RuntimeException: Could not execute entrypoint stage 'main'
+\tat net.fabricmc.loader.impl.FabricLoaderImpl.invokeEntrypoints(FabricLoaderImpl.java:403)
+\tat net.minecraft.client.main.Main.main(Main.java:215)
+Caused by: java.lang.OutOfMemoryError: Java heap space
+\t... 2 more
+
+A detailed walkthrough of the error, its code path and all known details is as follows:
+";
+
+	const JVM_REPORT: &str = "#
+# A fatal error has been detected by the Java Runtime Environment:
+#
+#  SIGSEGV (0xb) at pc=0x00007f0000000000, pid=1234, tid=1235
+#
+# Problematic frame:
+# C  [libnvidia-glcore.so.535.54.03+0xe0f1c4]
+#
+
+Native frames: (J=compiled Java code, j=interpreted, Vv=VM code, C=native code)
+C  [libnvidia-glcore.so.535.54.03+0xe0f1c4]
+C  [libGLX_nvidia.so.0+0x4a123]
+
+";
+
+	// Crash in JVM itself, while mesa driver is loaded
+	const JVM_REPORT_WITH_DRIVER: &str = "#
+# A fatal error has been detected by the Java Runtime Environment:
+#
+#  SIGSEGV (0xb) at pc=0x00007f0000000000, pid=1234, tid=1235
+#
+# Problematic frame:
+# V  [libjvm.so+0x5d1e2a]  G1ParScanThreadState::trim_queue()+0x2a
+#
+
+Native frames: (J=compiled Java code, j=interpreted, Vv=VM code, C=native code)
+V  [libjvm.so+0x5d1e2a]  G1ParScanThreadState::trim_queue()+0x2a
+V  [libjvm.so+0x5d3f10]  G1ParEvacuateFollowersClosure::do_void()+0x40
+
+Dynamic libraries:
+7f0000000000-7f0000100000 r-xp 00000000 08:01 123 /usr/lib/jvm/java-17/lib/server/libjvm.so
+7f0000200000-7f0000300000 r-xp 00000000 08:01 456 /usr/lib/x86_64-linux-gnu/libGL.so.1.7.0
+7f0000400000-7f0000500000 r-xp 00000000 08:01 789 /usr/lib/x86_64-linux-gnu/dri/iris_dri.so
+
+";
+
+	fn parse(name: &str, text: &str) -> CrashReport {
+		let path = get_temp_dir(&format!("crash-{name}")).join(name);
+		fs::write(&path, text).unwrap();
+		CrashReport::parse(&path).unwrap()
+	}
+
+	fn get_causes(text: &str) -> Vec<Cause> {
+		parse("latest.log", text).get_causes()
+	}
+
+	#[test]
+	fn parses_game_report() {
+		let report = parse("crash-2024-03-01_12.00.00-client.txt", GAME_REPORT);
+
+		assert_eq!(report.kind, ReportKind::Game);
+		assert_eq!(report.time.as_deref(), Some("2024-03-01 12:00:00"));
+		assert_eq!(report.description.as_deref(), Some("Initializing game"));
+		assert_eq!(
+			report.exception.as_deref(),
+			Some("java.lang.RuntimeException: Could not execute entrypoint stage 'main'")
+		);
+		assert_eq!(report.stack.len(), 4);
+		assert_eq!(report.get_causes(), [Cause::OutOfMemory]);
+	}
+
+	#[test]
+	fn parses_jvm_report() {
+		let report = parse("hs_err_pid1234.log", JVM_REPORT);
+
+		assert_eq!(report.kind, ReportKind::Jvm);
+		assert_eq!(
+			report.description.as_deref(),
+			Some("SIGSEGV (0xb) at pc=0x00007f0000000000, pid=1234, tid=1235")
+		);
+		assert_eq!(
+			report.exception.as_deref(),
+			Some("C  [libnvidia-glcore.so.535.54.03+0xe0f1c4]")
+		);
+		assert_eq!(report.stack.len(), 2);
+		assert_eq!(report.get_causes(), [Cause::GraphicsDriver]);
+	}
+
+	#[test]
+	fn parses_log() {
+		let report = parse(
+			"latest.log",
+			"[main/INFO]: Loading Minecraft 1.20.1
+Exception in thread \"main\" java.lang.IllegalStateException: GLFW error before init
+java.lang.IllegalStateException: GLFW error before init
+\tat com.mojang.blaze3d.platform.Window.<init>(Window.java:97)
+[main/INFO]: Stopping!
+",
+		);
+
+		assert_eq!(report.kind, ReportKind::Log);
+		assert_eq!(
+			report.exception.as_deref(),
+			Some("java.lang.IllegalStateException: GLFW error before init")
+		);
+		assert_eq!(report.stack.len(), 1);
+	}
+
+	#[test]
+	fn finds_only_new_reports() {
+		let instance_dir = get_temp_dir("crash-newest");
+		fs::create_dir_all(instance_dir.join(GAME_REPORTS_DIR)).unwrap();
+		fs::write(
+			instance_dir
+				.join(GAME_REPORTS_DIR)
+				.join("crash-1-client.txt"),
+			GAME_REPORT,
+		)
+		.unwrap();
+		let instance_dir = instance_dir.to_str().unwrap();
+
+		let report = CrashReport::find_newest(instance_dir, std::time::UNIX_EPOCH).unwrap();
+		assert_eq!(report.unwrap().kind, ReportKind::Game);
+
+		let later = SystemTime::now() + std::time::Duration::from_secs(3600);
+		assert!(CrashReport::find_newest(instance_dir, later)
+			.unwrap()
+			.is_none());
+	}
+
+	#[test]
+	fn detects_wrong_java() {
+		let text = "java.lang.UnsupportedClassVersionError: net/minecraft/client/main/Main has been compiled by a more recent version of the Java Runtime (class file version 65.0), this version of the Java Runtime only recognizes class file versions up to 61.0";
+
+		assert_eq!(get_causes(text), [Cause::WrongJava]);
+		assert!(Cause::WrongJava.describe(text).contains("needs java 21"));
+	}
+
+	#[test]
+	fn detects_old_version_on_new_java() {
+		let text = "java.lang.ClassCastException: class jdk.internal.loader.ClassLoaders$AppClassLoader cannot be cast to class java.net.URLClassLoader";
+
+		assert_eq!(get_causes(text), [Cause::WrongJava]);
+		assert!(Cause::WrongJava.describe(text).contains("needs java 8"));
+	}
+
+	#[test]
+	fn detects_java_too_new() {
+		assert_eq!(
+			get_causes(
+				"java.lang.IllegalArgumentException: Unsupported class file major version 65"
+			),
+			[Cause::JavaTooNew]
+		);
+	}
+
+	#[test]
+	fn detects_missing_dependency() {
+		let text = "net.fabricmc.loader.impl.FormattedException: Mod resolution encountered an incompatible mod set!
+A potential solution has been determined:
+\t - Install fabric-api, any version.
+Unmet dependency listing:
+\t - Mod 'Sodium Extra' (sodium-extra) 0.5.1 requires any version of sodium, which is missing!";
+
+		assert_eq!(get_causes(text), [Cause::MissingDependency]);
+		assert!(Cause::MissingDependency
+			.describe(text)
+			.contains("requires any version of sodium"));
+	}
+
+	#[test]
+	fn detects_out_of_memory() {
+		assert_eq!(
+			get_causes("java.lang.OutOfMemoryError: Java heap space"),
+			[Cause::OutOfMemory]
+		);
+	}
+
+	#[test]
+	fn detects_system_out_of_memory() {
+		assert_eq!(
+			get_causes(
+				"# There is insufficient memory for the Java Runtime Environment to continue."
+			),
+			[Cause::SystemOutOfMemory]
+		);
+		assert_eq!(
+			get_causes("Error occurred during initialization of VM\nCould not reserve enough space for object heap"),
+			[Cause::SystemOutOfMemory]
+		);
+	}
+
+	#[test]
+	fn detects_graphics_driver() {
+		assert_eq!(
+			get_causes("org.lwjgl.LWJGLException: Pixel format not accelerated"),
+			[Cause::GraphicsDriver]
+		);
+	}
+
+	#[test]
+	fn loaded_driver_is_not_blamed() {
+		let report = parse("hs_err_pid1234.log", JVM_REPORT_WITH_DRIVER);
+		assert!(report.get_causes().is_empty());
+
+		let report = parse(
+			"hs_err_pid1234.log",
+			&JVM_REPORT_WITH_DRIVER.replace("[libjvm.so", "[iris_dri.so"),
+		);
+		assert_eq!(report.get_causes(), [Cause::GraphicsDriver]);
+	}
+
+	#[test]
+	fn unknown_cause() {
+		assert!(
+			get_causes("java.lang.NullPointerException: Cannot invoke \"Object.toString()\"")
+				.is_empty()
+		);
+	}
+
+	#[test]
+	fn reads_required_java() {
+		assert_eq!(get_required_java("(class file version 65.0)"), Some(21));
+		assert_eq!(get_required_java("(class file version 52.0)"), Some(8));
+		assert_eq!(get_required_java("class file version unknown"), None);
+		assert_eq!(get_required_java("no version"), None);
+	}
+
+	#[test]
+	fn recognizes_exception_lines() {
+		assert!(is_exception_line(
+			"java.lang.IllegalStateException: message"
+		));
+		assert!(is_exception_line("\tjava.lang.OutOfMemoryError"));
+		assert!(!is_exception_line("[main/ERROR]: Exception in game"));
+		assert!(!is_exception_line(
+			"at java.lang.Thread.run(Thread.java:833)"
+		));
+	}
+}
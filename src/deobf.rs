@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::Path;
+
+use std::collections::HashMap;
+
+use crate::util::error::Error;
+
+/* DEOBFUSCATION
+* Mojang publishes mappings of obfuscated client in ProGuard format:
+* net.minecraft.client.Minecraft -> fud:
+*     java.io.File gameDirectory -> p
+*     1234:1240:void run() -> g
+* Class and member names of stack frames ("at fud.g(SourceFile:1236)"), qualified names
+* in messages ("fud.g()") and thrown exception classes are translated back
+* Several methods may share obfuscated name, line number of frame picks the right one
+* Bare short names aren't translated, since "a" or "is" may be just words
+* Mappings are taken for version from "Minecraft Version:" line of crash report, if it has one
+* Only vanilla names are obfuscated Mojang ones: Fabric remaps game to intermediary names
+* ("class_310") and older Forge to SRG ones, mappings don't know them
+*/
+
+#[derive(Default, Debug)]
+pub struct Mappings {
+	// By obfuscated name
+	classes: HashMap<String, ClassMapping>,
+}
+
+#[derive(Default, Debug)]
+struct ClassMapping {
+	name: String,
+	members: HashMap<String, Vec<MemberMapping>>,
+}
+
+#[derive(Default, Debug)]
+struct MemberMapping {
+	name: String,
+	// Lines of method in obfuscated class, none for fields
+	lines: Option<(u32, u32)>,
+}
+
+impl Mappings {
+	pub fn load(path: &Path) -> Result<Self, Error> {
+		Ok(Self::parse(&fs::read_to_string(path)?))
+	}
+
+	pub fn parse(text: &str) -> Self {
+		let mut mappings = Self::default();
+		let mut current: Option<&mut ClassMapping> = None;
+
+		for line in text.lines() {
+			if line.trim().is_empty() || line.trim_start().starts_with('#') {
+				continue;
+			}
+			let Some((original, obfuscated)) = line.split_once(" -> ") else {
+				continue;
+			};
+
+			// Members are indented
+			if !line.starts_with(char::is_whitespace) {
+				let obfuscated = obfuscated.trim().trim_end_matches(':').to_string();
+				current = Some(mappings.classes.entry(obfuscated).or_insert(ClassMapping {
+					name: original.trim().to_string(),
+					members: Default::default(),
+				}));
+				continue;
+			}
+
+			if let (Some(class), Some(member)) = (current.as_mut(), Self::parse_member(original)) {
+				class
+					.members
+					.entry(obfuscated.trim().to_string())
+					.or_default()
+					.push(member);
+			}
+		}
+
+		mappings
+	}
+
+	// "1234:1240:void run()", "1234:1240:void run():56:60" or "int field"
+	fn parse_member(original: &str) -> Option<MemberMapping> {
+		let mut declaration = original.trim();
+
+		let mut lines = None;
+		let mut parts = declaration.splitn(3, ':');
+		if let (Some(start), Some(end), Some(rest)) = (parts.next(), parts.next(), parts.next()) {
+			if let (Ok(start), Ok(end)) = (start.parse(), end.parse()) {
+				lines = Some((start, end));
+				declaration = rest;
+			}
+		}
+
+		let signature = declaration.split('(').next()?;
+		let name = signature.rsplit(' ').next()?;
+
+		Some(MemberMapping {
+			name: name.to_string(),
+			lines,
+		})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.classes.is_empty()
+	}
+
+	pub fn translate(&self, text: &str) -> String {
+		text.lines()
+			.map(|line| self.translate_line(line))
+			.collect::<Vec<String>>()
+			.join("\n")
+	}
+
+	fn translate_line(&self, line: &str) -> String {
+		let mut result = String::with_capacity(line.len());
+		let mut rest = line;
+
+		while let Some(start) = rest.find(is_name_char) {
+			result.push_str(&rest[..start]);
+			rest = &rest[start..];
+
+			let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+			// Trailing dot is the end of sentence
+			let token = rest[..end].trim_end_matches('.');
+			if token.is_empty() {
+				result.push_str(&rest[..end]);
+				rest = &rest[end..];
+				continue;
+			}
+			let after = &rest[token.len()..];
+
+			let is_exception =
+				result.trim().is_empty() || result.trim_end().ends_with("Caused by:");
+			result.push_str(&self.translate_token(token, after, is_exception));
+			rest = after;
+		}
+		result.push_str(rest);
+
+		result
+	}
+
+	fn translate_token(&self, token: &str, after: &str, is_exception: bool) -> String {
+		if let Some((owner, member)) = token.rsplit_once('.') {
+			if let Some(class) = self.classes.get(owner) {
+				return format!(
+					"{}.{}",
+					class.name,
+					class.get_member(member, get_frame_line(after))
+				);
+			}
+		}
+
+		// Thrown class, like "fud$a: message"
+		match self.classes.get(token) {
+			Some(class) if is_exception && (after.is_empty() || after.starts_with(':')) => {
+				class.name.clone()
+			}
+			_ => token.to_string(),
+		}
+	}
+}
+
+impl ClassMapping {
+	fn get_member(&self, obfuscated: &str, line: Option<u32>) -> String {
+		let Some(members) = self.members.get(obfuscated) else {
+			return obfuscated.to_string();
+		};
+
+		let by_line = line.and_then(|line| {
+			members.iter().find(|member| {
+				member
+					.lines
+					.is_some_and(|(start, end)| start <= line && line <= end)
+			})
+		});
+		if let Some(member) = by_line {
+			return member.name.clone();
+		}
+
+		let mut names: Vec<&str> = members.iter().map(|member| member.name.as_str()).collect();
+		names.sort();
+		names.dedup();
+		names.join("|")
+	}
+}
+
+// "Minecraft Version: 1.20.1" among system details of crash report
+pub fn get_game_version(text: &str) -> Option<&str> {
+	text.lines()
+		.find_map(|line| line.trim().strip_prefix("Minecraft Version: "))
+		.map(str::trim)
+		.filter(|version| !version.is_empty())
+}
+
+// "Is Modded: Definitely; Client brand changed to 'fabric'"
+pub fn is_modded(text: &str) -> bool {
+	text.lines()
+		.any(|line| line.trim().starts_with("Is Modded: Definitely"))
+}
+
+fn is_name_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+// "(SourceFile:1236)" after frame method
+fn get_frame_line(after: &str) -> Option<u32> {
+	let inside = after.strip_prefix('(')?.split(')').next()?;
+	inside.rsplit(':').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MAPPINGS: &str = "# compiler: R8
+net.minecraft.client.Minecraft -> fud:
+    java.io.File gameDirectory -> p
+    1234:1240:void run() -> g
+    1300:1310:void tick() -> g
+    100:105:boolean isRunning():56:61 -> h
+net.minecraft.client.Minecraft$ChatStatus -> fud$a:
+    10:12:boolean isChatAllowed(boolean) -> a
+net.minecraft.ReportedException -> w:
+    20:20:void <init>(net.minecraft.CrashReport) -> <init>
+";
+
+	fn translate(text: &str) -> String {
+		Mappings::parse(MAPPINGS).translate(text)
+	}
+
+	#[test]
+	fn picks_method_by_frame_line() {
+		assert_eq!(
+			translate("\tat fud.g(SourceFile:1236)"),
+			"\tat net.minecraft.client.Minecraft.run(SourceFile:1236)"
+		);
+		assert_eq!(
+			translate("\tat fud.g(SourceFile:1305)"),
+			"\tat net.minecraft.client.Minecraft.tick(SourceFile:1305)"
+		);
+		assert_eq!(
+			translate("\tat fud.h(SourceFile:101)"),
+			"\tat net.minecraft.client.Minecraft.isRunning(SourceFile:101)"
+		);
+	}
+
+	#[test]
+	fn lists_methods_without_line() {
+		assert_eq!(
+			translate("Exception in fud.g()"),
+			"Exception in net.minecraft.client.Minecraft.run|tick()"
+		);
+		assert_eq!(
+			translate("Reading fud.p."),
+			"Reading net.minecraft.client.Minecraft.gameDirectory."
+		);
+	}
+
+	#[test]
+	fn translates_thrown_classes() {
+		assert_eq!(
+			translate("w: Unexpected error"),
+			"net.minecraft.ReportedException: Unexpected error"
+		);
+		assert_eq!(
+			translate("Caused by: w: Unexpected error"),
+			"Caused by: net.minecraft.ReportedException: Unexpected error"
+		);
+	}
+
+	#[test]
+	fn translates_inner_classes() {
+		assert_eq!(
+			translate("fud$a: chat is disabled\n\tat fud$a.a(SourceFile:11)"),
+			"net.minecraft.client.Minecraft$ChatStatus: chat is disabled\n\tat net.minecraft.client.Minecraft$ChatStatus.isChatAllowed(SourceFile:11)"
+		);
+	}
+
+	#[test]
+	fn keeps_words() {
+		assert_eq!(translate("w is a word"), "w is a word");
+		assert_eq!(translate("Thread w: fud"), "Thread w: fud");
+	}
+
+	#[test]
+	fn reads_report_details() {
+		let report = "-- System Details --
+Details:
+\tMinecraft Version: 1.20.1
+\tMinecraft Version ID: 1.20.1
+\tIs Modded: Definitely; Client brand changed to 'fabric'
+";
+
+		assert_eq!(get_game_version(report), Some("1.20.1"));
+		assert!(is_modded(report));
+		assert_eq!(get_game_version("[main/INFO]: Stopping!"), None);
+		assert!(!is_modded(
+			"\tIs Modded: Probably not. Client jar signature and brand is untouched"
+		));
+	}
+}
@@ -1,4 +1,12 @@
 pub mod textfile;
 
+pub mod inheritance;
+pub mod logging;
+pub mod lwjgl;
+pub mod maven;
+pub mod modloader;
+pub mod natives;
+pub mod runtime;
+pub mod sources;
 pub mod vanilla;
 pub mod minecraft;
\ No newline at end of file
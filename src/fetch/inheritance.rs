@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use crate::util::error::Error;
+
+use super::maven::Coordinate;
+use super::vanilla::{ExecArgumentsArray, Library, Vanilla};
+
+/* INHERITANCE
+* Modloaders (and hand written versions) don't carry whole version package.
+* They have "inheritsFrom" field with id of parent version and only describe difference:
+* - libraries: child ones go first and replace parent ones with same group:artifact
+* - "mainClass": child overrides parent
+* - "arguments": child ones are appended to parent ones,
+*   "minecraftArguments" is a full string, so child one replaces parent one
+* - assets, downloads, logging, java version: taken from parent when child has none
+* Parent may have it's own parent, so whole chain is loaded and merged starting from the root version
+*/
+
+// Protection from versions, which inherit from each other
+const MAX_INHERITANCE_DEPTH: usize = 16;
+
+impl Vanilla {
+	// Loads whole chain of parents and merges it from the root down to this version
+	// Parents are loaded as they are, without resolving their own inheritance
+	pub fn resolve_inheritance(
+		self,
+		load_parent: impl Fn(&str) -> Result<Vanilla, Error>,
+	) -> Result<Self, Error> {
+		let mut chain = vec![self];
+
+		while let Some(parent_id) = chain
+			.last()
+			.and_then(|version| version.inherits_from.clone())
+		{
+			if chain.len() > MAX_INHERITANCE_DEPTH {
+				return Err(Error::Default(format!(
+					"version {} has too deep inheritance chain (more than {MAX_INHERITANCE_DEPTH})",
+					chain[0].id
+				)));
+			}
+			chain.push(load_parent(&parent_id)?);
+		}
+
+		// Client jar belongs to root version, unless launched version says otherwise
+		let root = chain.pop().expect("chain starts with this version");
+		let jar = chain
+			.first()
+			.and_then(|version| version.jar.clone())
+			.or(root.jar.clone())
+			.unwrap_or(root.id.clone());
+
+		let mut result = chain
+			.into_iter()
+			.rev()
+			.fold(root, |parent, child| child.inherit(parent));
+		result.jar = Some(jar);
+
+		Ok(result)
+	}
+
+	pub fn inherit(self, parent: Vanilla) -> Self {
+		let libraries = Self::merge_libraries(self.libraries, parent.libraries);
+
+		let arguments = match (parent.arguments, self.arguments) {
+			(Some(parent), Some(child)) => Some(ExecArgumentsArray {
+				game: parent.game.into_iter().chain(child.game).collect(),
+				jvm: parent.jvm.into_iter().chain(child.jvm).collect(),
+			}),
+			(parent, child) => child.or(parent),
+		};
+
+		Self {
+			asset_index: if self.asset_index.url.is_empty() {
+				parent.asset_index
+			} else {
+				self.asset_index
+			},
+			assets: if self.assets.is_empty() {
+				parent.assets
+			} else {
+				self.assets
+			},
+			downloads: if self.downloads.is_empty() {
+				parent.downloads
+			} else {
+				self.downloads
+			},
+			java_version: if self.java_version.major_version == 0 {
+				parent.java_version
+			} else {
+				self.java_version
+			},
+			logging: if self.logging.is_empty() {
+				parent.logging
+			} else {
+				self.logging
+			},
+			main_class: if self.main_class.is_empty() {
+				parent.main_class
+			} else {
+				self.main_class
+			},
+			minecraft_arguments: self.minecraft_arguments.or(parent.minecraft_arguments),
+			release_time: if self.release_time.is_empty() {
+				parent.release_time
+			} else {
+				self.release_time
+			},
+			r#type: if self.r#type.is_empty() {
+				parent.r#type
+			} else {
+				self.r#type
+			},
+			jar: self.jar.or(parent.jar),
+			inherits_from: parent.inherits_from,
+			id: self.id,
+			libraries,
+			arguments,
+		}
+	}
+
+	fn merge_libraries(child: Vec<Library>, parent: Vec<Library>) -> Vec<Library> {
+		let overridden: HashSet<String> = child
+			.iter()
+			.map(|library| Self::library_key(&library.name))
+			.collect();
+
+		child
+			.into_iter()
+			.chain(
+				parent
+					.into_iter()
+					.filter(|library| !overridden.contains(&Self::library_key(&library.name))),
+			)
+			.collect()
+	}
+
+	// "group:artifact:version[:classifier][@extension]" -> "group:artifact[:classifier]"
+	// Classifier is a part of the key, because natives share group:artifact with their jar
+	fn library_key(name: &str) -> String {
+		let Some(coordinate) = Coordinate::parse(name) else {
+			return name.to_string();
+		};
+
+		match coordinate.classifier {
+			Some(classifier) => {
+				format!("{}:{}:{classifier}", coordinate.group, coordinate.artifact)
+			}
+			None => format!("{}:{}", coordinate.group, coordinate.artifact),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(json: serde_json::Value) -> Vanilla {
+		serde_json::from_value(json).unwrap()
+	}
+
+	// child -> loader -> 1.20.1, loader points its own jar
+	fn load_parent(id: &str) -> Result<Vanilla, Error> {
+		match id {
+			"loader" => Ok(version(serde_json::json!({
+				"id": "loader",
+				"inheritsFrom": "1.20.1",
+				"jar": "loader",
+				"mainClass": "loader.Main",
+				"libraries": [
+					{ "name": "org.ow2.asm:asm:9.6" },
+					{ "name": "loader:loader:1.0" }
+				],
+				"arguments": { "game": ["--loader"], "jvm": [] }
+			}))),
+			"1.20.1" => Ok(version(serde_json::json!({
+				"id": "1.20.1",
+				"assets": "5",
+				"mainClass": "net.minecraft.client.main.Main",
+				"javaVersion": { "component": "java-runtime-gamma", "majorVersion": 17 },
+				"libraries": [
+					{ "name": "org.ow2.asm:asm:9.3" },
+					{ "name": "org.lwjgl:lwjgl:3.3.1" },
+					{ "name": "org.lwjgl:lwjgl:3.3.1:natives-linux" }
+				],
+				"arguments": { "game": ["--username"], "jvm": ["-cp"] }
+			}))),
+			_ => Err(Error::Default(format!("unknown version {id}"))),
+		}
+	}
+
+	fn child() -> Vanilla {
+		version(serde_json::json!({
+			"id": "child",
+			"inheritsFrom": "loader",
+			"libraries": [{ "name": "org.lwjgl:lwjgl:3.3.3" }],
+			"arguments": { "game": ["--child"], "jvm": [] }
+		}))
+	}
+
+	#[test]
+	fn merges_three_levels_from_root() {
+		let result = child().resolve_inheritance(load_parent).unwrap();
+
+		assert_eq!(result.id, "child");
+		assert_eq!(result.inherits_from, None);
+		assert_eq!(result.main_class, "loader.Main");
+		assert_eq!(result.assets, "5");
+		assert_eq!(result.java_version.major_version, 17);
+
+		let libraries: Vec<&str> = result
+			.libraries
+			.iter()
+			.map(|library| library.name.as_str())
+			.collect();
+		assert_eq!(
+			libraries,
+			[
+				"org.lwjgl:lwjgl:3.3.3",
+				"org.ow2.asm:asm:9.6",
+				"loader:loader:1.0",
+				"org.lwjgl:lwjgl:3.3.1:natives-linux",
+			]
+		);
+
+		let arguments = serde_json::to_value(result.arguments.unwrap().game).unwrap();
+		assert_eq!(
+			arguments,
+			serde_json::json!(["--username", "--loader", "--child"])
+		);
+	}
+
+	#[test]
+	fn jar_belongs_to_root() {
+		let result = child().resolve_inheritance(load_parent).unwrap();
+		assert_eq!(result.jar.as_deref(), Some("1.20.1"));
+	}
+
+	#[test]
+	fn jar_of_launched_version_wins() {
+		let mut child = child();
+		child.jar = Some(String::from("custom"));
+
+		let result = child.resolve_inheritance(load_parent).unwrap();
+		assert_eq!(result.jar.as_deref(), Some("custom"));
+	}
+
+	#[test]
+	fn inheritance_cycle_fails() {
+		let looped = |id: &str| Ok(version(serde_json::json!({ "id": id, "inheritsFrom": id })));
+		assert!(child().resolve_inheritance(looped).is_err());
+	}
+
+	#[test]
+	fn library_key_keeps_classifier() {
+		assert_eq!(
+			Vanilla::library_key("org.lwjgl:lwjgl:3.3.1"),
+			"org.lwjgl:lwjgl"
+		);
+		assert_eq!(
+			Vanilla::library_key("org.lwjgl:lwjgl:3.3.1:natives-linux@jar"),
+			"org.lwjgl:lwjgl:natives-linux"
+		);
+	}
+}
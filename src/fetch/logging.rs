@@ -0,0 +1,275 @@
+use super::vanilla::{DataObject, Vanilla};
+
+/* LOGGING
+* Version package has "logging.client" with log4j configuration file and jvm argument
+* ("-Dlog4j.configurationFile=${path}"), which points game to it
+* Versions 1.7 - 1.18 are vulnerable to Log4Shell (CVE-2021-44228). Mojang's mitigations:
+* - 1.7 - 1.11.2: patched configuration log4j2_17-111.xml
+* - 1.12 - 1.16.5: patched configuration log4j2_112-116.xml
+* - 1.17 - 1.18: -Dlog4j2.formatMsgNoLookups=true
+* 1.18.1 and newer are shipped with fixed log4j
+*/
+
+const URL_LOG4J_17_111: &str =
+	"https://launcher.mojang.com/v1/objects/4bb89a97a66f350bc9f73b3ca8509632682aea2e/log4j2_17-111.xml";
+const URL_LOG4J_112_116: &str =
+	"https://launcher.mojang.com/v1/objects/02937d122c86ce73319ef9975b58896fc1b491d1/log4j2_112-116.xml";
+
+const ARGUMENT_NO_LOOKUPS: &str = "-Dlog4j2.formatMsgNoLookups=true";
+
+// For snapshots, which ids can't be compared: release times of first snapshots
+// of 1.7 (13w36a), 1.12 (17w06a), 1.17 (20w45a) and release time of 1.18.1
+const TIME_1_7: &str = "2013-09-05";
+const TIME_1_12: &str = "2017-02-08";
+const TIME_1_17: &str = "2020-11-04";
+const TIME_1_18_1: &str = "2021-12-10";
+
+#[derive(PartialEq, Debug)]
+pub enum Log4jMitigation {
+	None,
+	// [file id] [url]
+	Config(&'static str, &'static str),
+	NoLookups,
+}
+
+impl Log4jMitigation {
+	pub fn for_version(version_id: &str, release_time: &str) -> Self {
+		match Self::parse_release(version_id) {
+			Some((1, minor, patch)) => match (minor, patch) {
+				(7..=11, _) => Self::config_17_111(),
+				(12..=16, _) => Self::config_112_116(),
+				(17, _) | (18, 0) => Self::NoLookups,
+				_ => Self::None,
+			},
+			Some(_) => Self::None,
+			None => Self::for_release_time(release_time),
+		}
+	}
+
+	fn for_release_time(release_time: &str) -> Self {
+		// ISO 8601 dates are comparable as strings
+		if !(TIME_1_7..TIME_1_18_1).contains(&release_time) {
+			Self::None
+		} else if release_time < TIME_1_12 {
+			Self::config_17_111()
+		} else if release_time < TIME_1_17 {
+			Self::config_112_116()
+		} else {
+			Self::NoLookups
+		}
+	}
+
+	fn config_17_111() -> Self {
+		Self::Config("log4j2_17-111.xml", URL_LOG4J_17_111)
+	}
+
+	fn config_112_116() -> Self {
+		Self::Config("log4j2_112-116.xml", URL_LOG4J_112_116)
+	}
+
+	// "1.16.5" -> (1, 16, 5), "1.18-pre1" -> (1, 18, 0), "21w37a" -> None
+	fn parse_release(version_id: &str) -> Option<(u32, u32, u32)> {
+		let release = version_id.split('-').next()?;
+		let mut numbers = release.split('.').map(|number| number.parse::<u32>().ok());
+
+		let major = numbers.next()??;
+		let minor = numbers.next()??;
+		let patch = match numbers.next() {
+			Some(patch) => patch?,
+			None => 0,
+		};
+
+		if numbers.next().is_some() {
+			return None;
+		}
+
+		Some((major, minor, patch))
+	}
+}
+
+impl Vanilla {
+	pub fn get_log4j_mitigation(&self) -> Log4jMitigation {
+		// Modloaders have their own ids, so version of client jar is used
+		Log4jMitigation::for_version(self.get_jar_id(), &self.release_time)
+	}
+
+	// Logging configuration file, with patched one in place of vulnerable
+	pub fn get_logging_file(&self, assets_dir: &str) -> Option<DataObject> {
+		let logging = self.logging.get("client")?;
+
+		let file = match self.get_log4j_mitigation() {
+			Log4jMitigation::Config(id, url) => DataObject {
+				path: id.to_string(),
+				size: 0,
+				url: url.to_string(),
+				// Mojang keeps sha1 of objects in their urls
+				hash: url.rsplit('/').nth(1).unwrap_or_default().into(),
+			},
+			_ => logging.file.clone(),
+		};
+
+		Some(DataObject {
+			path: format!(
+				"{}/objects/{}/{}/{}",
+				assets_dir,
+				&file.hash[0..2],
+				file.hash,
+				file.path
+			),
+			..file
+		})
+	}
+
+	pub fn get_logging_arguments(&self, assets_dir: &str) -> Vec<String> {
+		let mut arguments: Vec<String> = Default::default();
+
+		if let (Some(logging), Some(file)) = (
+			self.logging.get("client"),
+			self.get_logging_file(assets_dir),
+		) {
+			arguments.push(logging.argument.replace("${path}", &file.path));
+		}
+
+		if self.get_log4j_mitigation() == Log4jMitigation::NoLookups {
+			arguments.push(ARGUMENT_NO_LOOKUPS.to_string());
+		}
+
+		arguments
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(id: &str, file: &str, url: &str) -> Vanilla {
+		serde_json::from_value(serde_json::json!({
+			"id": id,
+			"releaseTime": "2017-09-18T08:39:46+00:00",
+			"logging": {
+				"client": {
+					"argument": "-Dlog4j.configurationFile=${path}",
+					"file": { "id": file, "sha1": url.rsplit('/').nth(1).unwrap(), "size": 888, "url": url },
+					"type": "log4j2-xml"
+				}
+			}
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn mitigation_by_version_id() {
+		for id in ["1.7", "1.7.10", "1.9.4", "1.11.2"] {
+			assert_eq!(
+				Log4jMitigation::for_version(id, ""),
+				Log4jMitigation::config_17_111(),
+				"{id}"
+			);
+		}
+		for id in ["1.12", "1.12.2", "1.15-pre1", "1.16.5"] {
+			assert_eq!(
+				Log4jMitigation::for_version(id, ""),
+				Log4jMitigation::config_112_116(),
+				"{id}"
+			);
+		}
+		for id in ["1.17", "1.17.1", "1.18", "1.18-rc3"] {
+			assert_eq!(
+				Log4jMitigation::for_version(id, ""),
+				Log4jMitigation::NoLookups,
+				"{id}"
+			);
+		}
+		for id in ["1.6.4", "1.18.1", "1.18.2", "1.20.1", "1.21"] {
+			assert_eq!(
+				Log4jMitigation::for_version(id, ""),
+				Log4jMitigation::None,
+				"{id}"
+			);
+		}
+	}
+
+	#[test]
+	fn mitigation_by_release_time() {
+		let mitigation = |time| Log4jMitigation::for_version("21w37a", time);
+
+		assert_eq!(
+			mitigation("2013-04-18T15:00:00+00:00"),
+			Log4jMitigation::None
+		);
+		assert_eq!(
+			mitigation("2016-11-10T10:00:00+00:00"),
+			Log4jMitigation::config_17_111()
+		);
+		assert_eq!(
+			mitigation("2019-07-03T12:00:00+00:00"),
+			Log4jMitigation::config_112_116()
+		);
+		assert_eq!(
+			mitigation("2021-09-15T15:00:00+00:00"),
+			Log4jMitigation::NoLookups
+		);
+		assert_eq!(
+			mitigation("2022-01-19T15:00:00+00:00"),
+			Log4jMitigation::None
+		);
+	}
+
+	#[test]
+	fn vulnerable_config_is_replaced() {
+		let version = version(
+			"1.12.2",
+			"client-1.12.xml",
+			"https://launcher.mojang.com/v1/objects/ef4f57b922df243d0cef096efe808c72db042149/client-1.12.xml",
+		);
+
+		assert_eq!(
+			version.get_logging_arguments("assets"),
+			["-Dlog4j.configurationFile=assets/objects/02/02937d122c86ce73319ef9975b58896fc1b491d1/log4j2_112-116.xml"]
+		);
+	}
+
+	#[test]
+	fn patched_config_is_kept() {
+		// Version packages, which Mojang updated after Log4Shell, already point to patched file
+		let version = version("1.12.2", "log4j2_112-116.xml", URL_LOG4J_112_116);
+
+		let file = version.get_logging_file("assets").unwrap();
+		assert_eq!(file.url, URL_LOG4J_112_116);
+		assert_eq!(
+			version.get_logging_arguments("assets"),
+			["-Dlog4j.configurationFile=assets/objects/02/02937d122c86ce73319ef9975b58896fc1b491d1/log4j2_112-116.xml"]
+		);
+	}
+
+	#[test]
+	fn fixed_versions_keep_own_config() {
+		let version = version(
+			"1.18.2",
+			"client-1.12.xml",
+			"https://piston-data.mojang.com/v1/objects/bd65e7d2e3c237be76cfbef4c2405033d7f91521/client-1.12.xml",
+		);
+
+		assert_eq!(
+			version.get_logging_arguments("assets"),
+			["-Dlog4j.configurationFile=assets/objects/bd/bd65e7d2e3c237be76cfbef4c2405033d7f91521/client-1.12.xml"]
+		);
+	}
+
+	#[test]
+	fn no_lookups_for_1_17() {
+		let version = version(
+			"1.17.1",
+			"client-1.12.xml",
+			"https://piston-data.mojang.com/v1/objects/bd65e7d2e3c237be76cfbef4c2405033d7f91521/client-1.12.xml",
+		);
+
+		assert_eq!(
+			version.get_logging_arguments("assets"),
+			[
+				"-Dlog4j.configurationFile=assets/objects/bd/bd65e7d2e3c237be76cfbef4c2405033d7f91521/client-1.12.xml",
+				ARGUMENT_NO_LOOKUPS,
+			]
+		);
+	}
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::Path;
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::error::Error;
+
+use super::vanilla::{Library, OSArch, OSName};
+
+/* LWJGL REPLACEMENTS
+* Official version packages have natives only for x86 hosts (and arm64 macs since 1.19)
+* Optional table allows to swap libraries for compatible builds on other platforms:
+* {
+*   "linux-arm64": {
+*     "org.lwjgl:lwjgl:3.2.2": [ ...libraries in version package format... ],
+*     ...
+*   },
+*   ...
+* }
+* Key of the first level is [OS name]-[arch], key of the second - name of replaced library
+*/
+
+const REPLACEMENTS_PATH: &str = "data/lwjgl_replacements.json";
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LwjglReplacements {
+	#[serde(flatten)]
+	pub hosts: HashMap<String, HashMap<String, Vec<Library>>>,
+}
+
+impl LwjglReplacements {
+	// Absence of table is not an error
+	pub fn load() -> Result<Self, Error> {
+		let path = Path::new(REPLACEMENTS_PATH);
+		if !path.exists() {
+			return Ok(Self::default());
+		}
+
+		Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+	}
+
+	pub fn apply(&self, libraries: &mut Vec<Library>) {
+		let host = format!(
+			"{}-{}",
+			OSName::current().as_str(),
+			OSArch::current().as_str()
+		);
+		let Some(table) = self.hosts.get(&host) else {
+			return;
+		};
+
+		let mut replaced: Vec<Library> = Vec::with_capacity(libraries.len());
+
+		for library in libraries.drain(..) {
+			match table.get(&library.name) {
+				Some(replacements) => replaced.extend(replacements.iter().cloned()),
+				None => replaced.push(library),
+			}
+		}
+
+		*libraries = replaced;
+	}
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use reqwest::blocking::Client;
+
+use crate::util::error::Error;
+
+use super::textfile::RetrievePlainText;
+use super::vanilla::DataObject;
+
+/* MAVEN
+* Modloaders describe libraries by coordinate "group:artifact:version[:classifier][@extension]"
+* and repository url. Inside of repository artifact lies in:
+* - [group as path]/[artifact]/[version]/[artifact]-[version][-classifier].[extension]
+* Every file has "[file].sha1" next to it, which is used when library has no hash
+* - [group as path]/[artifact]/maven-metadata.xml: list of published versions
+* Metadata has no checksums of files (only versions and snapshot builds),
+* so it's not a source of hashes, sidecar is the only one repositories publish
+*/
+
+const DEFAULT_EXTENSION: &str = "jar";
+
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct Coordinate {
+	pub group: String,
+	pub artifact: String,
+	pub version: String,
+	pub classifier: Option<String>,
+	pub extension: String,
+}
+
+struct Metadata;
+
+impl Coordinate {
+	pub fn parse(name: &str) -> Option<Self> {
+		let (name, extension) = match name.split_once('@') {
+			Some((name, extension)) => (name, extension),
+			None => (name, DEFAULT_EXTENSION),
+		};
+
+		let mut parts = name.split(':');
+		let group = parts.next().filter(|part| !part.is_empty())?;
+		let artifact = parts.next().filter(|part| !part.is_empty())?;
+		let version = parts.next().filter(|part| !part.is_empty())?;
+		let classifier = parts.next().map(String::from);
+
+		if parts.next().is_some() {
+			return None;
+		}
+
+		Some(Self {
+			group: group.to_string(),
+			artifact: artifact.to_string(),
+			version: version.to_string(),
+			classifier,
+			extension: extension.to_string(),
+		})
+	}
+
+	// [artifact]-[version][-classifier].[extension]
+	pub fn get_file_name(&self) -> String {
+		match self.classifier.as_ref() {
+			Some(classifier) => format!(
+				"{}-{}-{classifier}.{}",
+				self.artifact, self.version, self.extension
+			),
+			None => format!("{}-{}.{}", self.artifact, self.version, self.extension),
+		}
+	}
+
+	// Path relative to repository root or libraries directory
+	pub fn get_path(&self) -> String {
+		format!(
+			"{}/{}/{}/{}",
+			self.group.replace('.', "/"),
+			self.artifact,
+			self.version,
+			self.get_file_name()
+		)
+	}
+
+	pub fn get_url(&self, repository: &str) -> String {
+		format!("{}/{}", repository.trim_end_matches('/'), self.get_path())
+	}
+
+	// Empty repository means that artifact isn't downloadable (bundled into installer)
+	pub fn get_object(&self, repository: &str, hash: Option<&str>, size: usize) -> DataObject {
+		DataObject {
+			path: self.get_path(),
+			size,
+			url: match repository.is_empty() {
+				true => String::new(),
+				false => self.get_url(repository),
+			},
+			hash: hash.unwrap_or_default().into(),
+		}
+	}
+}
+
+// Asks repository for sha1 of object without it. Object, that already exists, is trusted,
+// so it doesn't cost request on every launch
+pub fn fill_checksum(object: &mut DataObject) {
+	if !object.hash.is_empty() || object.url.is_empty() || Path::new(&object.path).exists() {
+		return;
+	}
+
+	if let Some(hash) = get_checksum(&object.url) {
+		object.hash = hash.into();
+	}
+}
+
+// Contents of "[url].sha1", it may be followed by file name
+pub fn get_checksum(url: &str) -> Option<String> {
+	let response = Client::new().get(format!("{url}.sha1")).send().ok()?;
+	if !response.status().is_success() {
+		return None;
+	}
+
+	let hash = response.text().ok()?;
+	let hash = hash.split_whitespace().next()?;
+
+	match hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+		true => Some(hash.to_lowercase()),
+		false => None,
+	}
+}
+
+// Oldest versions go first
+pub fn get_versions(repository: &str, group: &str, artifact: &str) -> Result<Vec<String>, Error> {
+	let url = format!(
+		"{}/{}/{artifact}/maven-metadata.xml",
+		repository.trim_end_matches('/'),
+		group.replace('.', "/")
+	);
+	let path = format!("data/maven/{group}/{artifact}/maven-metadata.xml");
+
+	// Saved metadata is reused until it's checksum changes
+	let hash = get_checksum(&url);
+	let text = Metadata::retrieve_text(&path, &url, hash.as_ref())?;
+
+	Ok(text
+		.split("<version>")
+		.skip(1)
+		.filter_map(|part| part.split("</version>").next())
+		.map(|version| version.trim().to_string())
+		.collect())
+}
+impl RetrievePlainText for Metadata {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_coordinate() {
+		let coordinate = Coordinate::parse("org.lwjgl:lwjgl:3.3.1:natives-linux@zip").unwrap();
+
+		assert_eq!(coordinate.group, "org.lwjgl");
+		assert_eq!(coordinate.classifier.as_deref(), Some("natives-linux"));
+		assert_eq!(
+			coordinate.get_path(),
+			"org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.zip"
+		);
+		assert_eq!(
+			coordinate.get_url("https://libraries.minecraft.net/"),
+			"https://libraries.minecraft.net/org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.zip"
+		);
+	}
+
+	#[test]
+	fn default_extension_is_jar() {
+		let coordinate = Coordinate::parse("net.fabricmc:fabric-loader:0.15.7").unwrap();
+
+		assert_eq!(
+			coordinate.get_path(),
+			"net/fabricmc/fabric-loader/0.15.7/fabric-loader-0.15.7.jar"
+		);
+	}
+
+	#[test]
+	fn wrong_coordinates() {
+		assert_eq!(Coordinate::parse("net.fabricmc:fabric-loader"), None);
+		assert_eq!(Coordinate::parse("a::1"), None);
+		assert_eq!(Coordinate::parse("a:b:1:c:d"), None);
+	}
+
+	#[test]
+	fn bundled_object_has_no_url() {
+		let coordinate =
+			Coordinate::parse("net.minecraftforge:forge:1.20.1-47.2.0:client").unwrap();
+
+		assert!(coordinate.get_object("", None, 0).url.is_empty());
+	}
+}
@@ -8,13 +8,25 @@ use reqwest::blocking::Client;
 
 use indicatif::{ProgressBar, ProgressStyle};
 
+use crate::auth::yggdrasil::{AuthlibInjector, INJECTOR_ARTIFACT_URL};
+use crate::auth::AuthSession;
+use crate::instance::Instance;
+use crate::java::JavaRuntime;
+use crate::jvm::{HeapSize, JvmPreset, DEFAULT_GAME_MEMORY};
+use crate::supervisor::{SupervisedGame, Supervisor, DEFAULT_KEPT_LOGS};
 use crate::util::error::Error;
 
-use super::vanilla::{DataObject, LaunchArgumentsType, Manifest, Vanilla};
+use super::modloader::ModLoader;
+use super::vanilla::{
+	get_class_path_separator, DataObject, LaunchArgumentsType, Manifest, Vanilla,
+};
+
+const LAUNCHER_NAME: &str = "rostermine";
 
 pub struct Minecraft {
 	// Required for certain checks
 	package: Vanilla,
+	loader: Box<dyn ModLoader>,
 
 	instance_dir: String,
 
@@ -24,13 +36,19 @@ pub struct Minecraft {
 }
 
 impl Minecraft {
-	pub fn new(data_dir: String, instance_dir: String, version_id: &String) -> Result<Self, Error> {
-		let manifest = Manifest::new().unwrap();
-		let version_manifest = manifest.get_for_version(version_id);
-		//			.expect("Failed to find sufficient minecraft version");
+	pub fn new(
+		data_dir: String,
+		instance_dir: String,
+		version_id: &str,
+		loader: Box<dyn ModLoader>,
+		manifest: &Manifest,
+	) -> Result<Self, Error> {
+		// Modloader profile becomes local version, which inherits from vanilla one
+		let version_id = loader.install_profile(manifest.resolve_alias(version_id))?;
 
 		Ok(Self {
-			package: Vanilla::new(&version_manifest)?,
+			package: Vanilla::load(&version_id, manifest)?,
+			loader,
 			instance_dir,
 			assets_dir: format!("{data_dir}/assets"),
 			libraries_dir: format!("{data_dir}/libraries"),
@@ -38,10 +56,17 @@ impl Minecraft {
 		})
 	}
 
-	pub fn update(&self) -> Result<(), Error> {
-		let client = Client::new();
+	pub fn get_java_major_version(&self) -> u32 {
+		self.package.java_version.major_version.max(0) as u32
+	}
+
+	pub fn get_java_component(&self) -> &str {
+		&self.package.java_version.component
+	}
 
-		let objects = self.package.get_data_objects()?;
+	pub fn update(&self, java: &JavaRuntime) -> Result<(), Error> {
+		let mut objects = self.package.get_data_objects()?;
+		objects.extend(self.loader.get_data_objects(&self.package)?);
 
 		let mut size = 0;
 		for object in &objects {
@@ -49,34 +74,55 @@ impl Minecraft {
 		}
 		println!("Size inside storage: {} MB", size as f32 / 1048576f32);
 
+		println!("Checking storage. . .");
+		Self::download_objects(&objects)?;
+
+		self.package.extract_natives()?;
+
+		self.loader.install(&self.package, java)
+	}
+
+	// Downloads everything, that is missing or differs from it's hash
+	pub fn download_objects(objects: &[DataObject]) -> Result<(), Error> {
+		let client = Client::new();
+
 		let bar = ProgressBar::new(objects.len() as u64).with_style(
-			ProgressStyle::with_template(&"[{elapsed_precise}] {bar:20} {pos:>5}/{len} {msg}")
+			ProgressStyle::with_template("[{elapsed_precise}] {bar:20} {pos:>5}/{len} {msg}")
 				.expect("error in... Progress bar styling :/"),
 		);
 
-		println!("Checking storage. . .");
-		for object in &objects {
-			Self::update_task(&client, &bar, &object);
+		for object in objects {
+			Self::update_task(&client, &bar, object)?;
 			bar.inc(1);
 		}
 
 		bar.set_message("DONE!");
 		bar.finish();
 
-		self.package.extract_natives()?;
-
 		Ok(())
 	}
 
-	fn update_task(client: &Client, bar: &ProgressBar, object: &DataObject) {
+	fn update_task(client: &Client, bar: &ProgressBar, object: &DataObject) -> Result<(), Error> {
 		if object.is_cached() {
 			if bar.message() != "OK" {
 				bar.set_message("OK");
 			}
-			return;
+			return Ok(());
 		}
 
 		let path = Path::new(&object.path);
+
+		// Bundled into installer or produced by it
+		if object.url.is_empty() {
+			return match path.exists() {
+				true => Ok(()),
+				false => Err(Error::Default(format!(
+					"{} is missing and has no download url",
+					object.path
+				))),
+			};
+		}
+
 		fs::create_dir_all(path.parent().unwrap()).expect("failed to create dir for data object");
 
 		bar.set_message(format!("GET {}", object.url));
@@ -91,98 +137,291 @@ impl Minecraft {
 				Err(e) => bar.set_message(format!("ERROR: {e}\nRetrying. . .")),
 			}
 		}
+
+		// Objects without hash can't be verified
+		if !object.hash.is_empty() && !object.is_cached() {
+			return Err(Error::Default(format!(
+				"hash mismatch for {} downloaded from {}",
+				object.path, object.url
+			)));
+		}
+
+		Ok(())
+	}
+
+	pub fn launch(
+		&self,
+		java: &JavaRuntime,
+		instance: &Instance,
+		session: &AuthSession,
+	) -> Result<SupervisedGame, Error> {
+		let mut command = self.get_command(java, instance, session)?;
+		Self::get_supervisor(instance, &self.instance_dir).spawn(&mut command)
+	}
+
+	// Game outlives launcher, so only it's PID is returned
+	pub fn launch_detached(
+		&self,
+		java: &JavaRuntime,
+		instance: &Instance,
+		session: &AuthSession,
+	) -> Result<u32, Error> {
+		let mut command = self.get_command(java, instance, session)?;
+		Self::get_supervisor(instance, &self.instance_dir).spawn_detached(&mut command)
+	}
+
+	pub fn get_id(&self) -> &str {
+		&self.package.id
+	}
+
+	fn get_supervisor(instance: &Instance, instance_dir: &str) -> Supervisor {
+		Supervisor::new(instance_dir, instance.keep_logs.unwrap_or(DEFAULT_KEPT_LOGS))
 	}
 
-	pub fn launch(&self) -> Result<(), Error> {
+	// Instance adds own arguments and environment
+	fn get_command(
+		&self,
+		java: &JavaRuntime,
+		instance: &Instance,
+		session: &AuthSession,
+	) -> Result<Command, Error> {
 		let class_path = self.package.get_class_path(&self.libraries_dir, &self.versions_dir);
 
 		let main_class = &self.package.main_class;
 
 		let natives_directory = format!("{}/{}/natives", self.versions_dir, self.package.id);
 
-		let natives_override = [
-			format!("-Djava.library.path={natives_directory}"),
-			format!("-Djna.tmpdir={natives_directory}"),
-			format!("-Dorg.lwjgl.system.SharedLibraryExtractPath={natives_directory}"),
-			format!("-Dio.netty.native.workdir={natives_directory}"),
-		];
-
-		let jvm_arguments = vec!["-Xms1G", "-Xmx4G"];
-
-		let minecraft_jvm_arguments: Vec<&str> = self
+		let heap = HeapSize::parse(instance.memory.as_deref().unwrap_or(DEFAULT_GAME_MEMORY))?;
+		let preset = JvmPreset::from_name(instance.preset.as_deref().unwrap_or("default"))?;
+		let jvm_arguments = preset.get_arguments(heap.get_megabytes(&self.instance_dir), java);
+
+		// Third-party auth server replaces Mojang's ones inside of game
+		let agent_arguments = match session.auth_server.as_deref() {
+			Some(api_root) => AuthlibInjector::get_jvm_arguments(
+				&AuthlibInjector::install(&self.libraries_dir, INJECTOR_ARTIFACT_URL)?,
+				api_root,
+			),
+			None => Default::default(),
+		};
+
+		let logging_arguments = self.package.get_logging_arguments(&self.assets_dir);
+
+		// Placeholders may be a part of argument: "-DlibraryDirectory=${library_directory}"
+		let values: HashMap<&str, &str> = HashMap::from([
+			("natives_directory", natives_directory.as_str()),
+			("launcher_name", LAUNCHER_NAME),
+			("launcher_version", env!("CARGO_PKG_VERSION")),
+			("classpath", &class_path),
+			("classpath_separator", get_class_path_separator()),
+			("library_directory", &self.libraries_dir),
+			("auth_player_name", &session.name),
+			("version_name", &self.package.id),
+			("game_directory", &self.instance_dir),
+			("assets_root", &self.assets_dir),
+			("game_assets", &self.assets_dir),
+			("assets_index_name", &self.package.assets),
+			("auth_uuid", &session.uuid),
+			("auth_access_token", &session.access_token),
+			("auth_xuid", &session.xuid),
+			("user_type", &session.user_type),
+			("user_properties", "{}"),
+			("version_type", &self.package.r#type),
+		]);
+
+		let minecraft_jvm_arguments: Vec<String> = self
 			.package
 			.get_launch_arguments(LaunchArgumentsType::Jvm)
-			.unwrap_or(vec![natives_override[0].as_str(), "-cp", &class_path])
+			.unwrap_or(vec![
+				"-Djava.library.path=${natives_directory}",
+				"-cp",
+				"${classpath}",
+			])
 			.iter()
-			.map(|&argument| match argument {
-				"-Djava.library.path=${natives_directory}" => natives_override[0].as_str(),
-				"-Djna.tmpdir=${natives_directory}" => natives_override[1].as_str(),
-				"-Dorg.lwjgl.system.SharedLibraryExtractPath=${natives_directory}" => {
-					natives_override[2].as_str()
-				}
-				"-Dio.netty.native.workdir=${natives_directory}" => natives_override[3].as_str(),
-				"-Dminecraft.launcher.brand=${launcher_name}" => {
-					"-Dminecraft.launcher.brand=rostermine"
-				}
-				"-Dminecraft.launcher.version=${launcher_version}" => {
-					"-Dminecraft.launcher.version=0.1.0"
-				}
-
-				"${classpath}" => &class_path,
-
-				_ => argument,
-			})
+			.map(|argument| substitute(argument, &values))
 			.collect();
 
-		let minecraft_arguments: Vec<&str> = self
+		let minecraft_arguments: Vec<String> = self
 			.package
 			.get_launch_arguments(LaunchArgumentsType::Game)
 			.expect("could not launch minecraft. No launch arguments in version manifest")
 			.iter()
-			.map(|&argument| match argument {
-				"${auth_player_name}" => "Player", // Replace with real auth
-				"${version_name}" => &self.package.id,
-				"${game_directory}" => &self.instance_dir,
-				"${assets_root}" => &self.assets_dir,
-				"${game_assets}" => &self.assets_dir,
-				"${assets_index_name}" => &self.package.assets,
-				"${auth_uuid}" => "0",
-				"${auth_access_token}" => "0",
-				"${user_type}" => "offline",
-				"${user_properties}" => "{}",
-				"${version_type}" => &self.package.r#type,
-
-				_ => argument,
-			})
+			.map(|argument| substitute(argument, &values))
 			.collect();
 
 		let mut envs: HashMap<String, String> = Default::default();
 
-		match &std::env::consts::OS {
-			&"linux" => {
-				envs.insert(
-					String::from("LD_LIBRARY_PATH"),
-					match std::env::var("LD_LIBRARY_PATH") {
-						Ok(oldvar) => format!("{natives_directory}:{oldvar}"),
-						Err(_) => natives_directory,
-					},
-				);
-			}
-			_ => {}
+		if std::env::consts::OS == "linux" {
+			envs.insert(
+				String::from("LD_LIBRARY_PATH"),
+				match std::env::var("LD_LIBRARY_PATH") {
+					Ok(oldvar) => format!("{natives_directory}:{oldvar}"),
+					Err(_) => natives_directory.clone(),
+				},
+			);
 		}
 
+		envs.extend(instance.env.clone());
+
 		fs::create_dir_all(&self.instance_dir)?;
 
-		Command::new("java")
+		let mut command = Command::new(&java.executable);
+		command
 			.current_dir(&self.instance_dir)
 			.envs(envs)
 			.args(jvm_arguments)
+			.args(agent_arguments)
+			.args(&instance.jvm_arguments)
+			.args(logging_arguments)
 			.args(minecraft_jvm_arguments)
 			.arg(main_class)
 			.args(minecraft_arguments)
-			.spawn()?
-			.wait()?;
+			.args(&instance.game_arguments);
 
-		Ok(())
+		Ok(command)
+	}
+}
+
+// "${name}" anywhere inside of argument, unknown ones are kept as they are
+fn substitute(argument: &str, values: &HashMap<&str, &str>) -> String {
+	let mut result = String::with_capacity(argument.len());
+	let mut rest = argument;
+
+	while let Some(start) = rest.find("${") {
+		result.push_str(&rest[..start]);
+		rest = &rest[start..];
+
+		let Some(end) = rest.find('}') else {
+			break;
+		};
+		match values.get(&rest[2..end]) {
+			Some(value) => result.push_str(value),
+			None => result.push_str(&rest[..=end]),
+		}
+		rest = &rest[end + 1..];
+	}
+	result.push_str(rest);
+
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	use crate::fetch::modloader::VanillaLoader;
+	use crate::util::testing::get_temp_dir;
+
+	const VANILLA: &str = include_str!("../../tests/fixtures/1.20.1.json");
+	const FORGE: &str = include_str!("../../tests/fixtures/forge-1.20.1-47.2.0.json");
+
+	fn get_forge_arguments() -> (Vec<String>, PathBuf) {
+		let package: Vanilla = serde_json::from_str(FORGE).unwrap();
+		let package = package
+			.resolve_inheritance(|_| Ok(serde_json::from_str(VANILLA)?))
+			.unwrap();
+
+		let instance_dir = get_temp_dir("forge-instance");
+		let minecraft = Minecraft {
+			package,
+			loader: Box::new(VanillaLoader),
+			instance_dir: instance_dir.to_string_lossy().into_owned(),
+			assets_dir: String::from("/data/assets"),
+			libraries_dir: String::from("/data/libraries"),
+			versions_dir: String::from("/data/versions"),
+		};
+		let java = JavaRuntime {
+			executable: PathBuf::from("java"),
+			major_version: 17,
+		};
+
+		let command = minecraft
+			.get_command(
+				&java,
+				&Instance::default(),
+				&AuthSession::offline("Steve").unwrap(),
+			)
+			.unwrap();
+		let arguments = command
+			.get_args()
+			.map(|argument| argument.to_string_lossy().into_owned())
+			.collect();
+
+		(arguments, instance_dir)
+	}
+
+	#[test]
+	fn forge_command_line() {
+		let (arguments, instance_dir) = get_forge_arguments();
+		let separator = get_class_path_separator();
+		let after = |name: &str| {
+			let index = arguments
+				.iter()
+				.position(|argument| argument == name)
+				.unwrap();
+			arguments[index + 1].as_str()
+		};
+
+		for argument in &arguments {
+			assert!(!argument.contains("${"), "{argument} isn't substituted");
+		}
+
+		assert!(arguments.contains(&String::from("-DlibraryDirectory=/data/libraries")));
+		assert!(arguments.contains(&String::from(
+			"-Djava.library.path=/data/versions/1.20.1-forge-47.2.0/natives"
+		)));
+		assert!(arguments.contains(&String::from("-Dminecraft.launcher.brand=rostermine")));
+		assert!(arguments
+			.iter()
+			.any(|argument| argument.starts_with("-DignoreList=")
+				&& argument.ends_with(",forge-,1.20.1-forge-47.2.0.jar")));
+		assert_eq!(
+			after("-p"),
+			[
+				"/data/libraries/cpw/mods/bootstraplauncher/1.1.2/bootstraplauncher-1.1.2.jar",
+				"/data/libraries/cpw/mods/securejarhandler/2.1.10/securejarhandler-2.1.10.jar",
+				"/data/libraries/org/ow2/asm/asm/9.5/asm-9.5.jar",
+			]
+			.join(separator)
+		);
+
+		// Forge's asm replaces vanilla one, client jar is vanilla's
+		let class_path: Vec<&str> = after("-cp").split(separator).collect();
+		assert!(class_path.contains(&"/data/libraries/org/ow2/asm/asm/9.5/asm-9.5.jar"));
+		assert!(!class_path.contains(&"/data/libraries/org/ow2/asm/asm/9.3/asm-9.3.jar"));
+		assert!(class_path
+			.contains(&"/data/libraries/net/minecraft/client/1.20.1/client-1.20.1-official.jar"));
+
+		// Main class separates JVM arguments from game ones
+		let main_class = arguments
+			.iter()
+			.position(|argument| argument == "cpw.mods.bootstraplauncher.BootstrapLauncher")
+			.unwrap();
+		assert!(
+			main_class
+				> arguments
+					.iter()
+					.position(|argument| argument == "-cp")
+					.unwrap()
+		);
+		assert_eq!(after("--version"), "1.20.1-forge-47.2.0");
+		assert_eq!(after("--username"), "Steve");
+		assert_eq!(after("--assetIndex"), "5");
+		assert_eq!(after("--launchTarget"), "forgeclient");
+		assert_eq!(after("--gameDir"), instance_dir.to_string_lossy());
+
+		fs::remove_dir_all(instance_dir).unwrap();
+	}
+
+	#[test]
+	fn substitutes_inside_of_argument() {
+		let values = HashMap::from([("a", "1"), ("b", "2")]);
+
+		assert_eq!(substitute("${a}", &values), "1");
+		assert_eq!(substitute("-Dx=${a}/lib${b}${a}", &values), "-Dx=1/lib21");
+		assert_eq!(substitute("${unknown}:${a}", &values), "${unknown}:1");
+		assert_eq!(substitute("${a", &values), "${a");
+		assert_eq!(substitute("plain", &values), "plain");
 	}
 }
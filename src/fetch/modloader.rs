@@ -1 +1,80 @@
-pub mod fabric;
\ No newline at end of file
+pub mod fabric;
+pub mod forge;
+pub mod quilt;
+
+use crate::java::JavaRuntime;
+use crate::util::error::Error;
+
+use super::vanilla::{DataObject, Vanilla};
+
+use fabric::FabricLoader;
+use forge::{Flavour, ForgeLoader};
+
+/* MODLOADERS
+* Every loader turns game version into version profile inside of data/versions,
+* which inherits from vanilla one, so it's launched like any other local version
+* Loader may also need own files and some work after everything is downloaded
+* (forge patches client jar with processors)
+*/
+
+pub trait ModLoader {
+	fn get_name(&self) -> &'static str;
+
+	// Newest loader versions go first
+	fn list_versions(&self, game_version: &str) -> Result<Vec<String>, Error>;
+
+	// Saves overlay profile into data/versions and returns it's id
+	fn install_profile(&self, game_version: &str) -> Result<String, Error>;
+
+	// Files, that aren't listed in profile libraries
+	fn get_data_objects(&self, _profile: &Vanilla) -> Result<Vec<DataObject>, Error> {
+		Ok(Default::default())
+	}
+
+	// Called after profile files were downloaded
+	fn install(&self, _profile: &Vanilla, _java: &JavaRuntime) -> Result<(), Error> {
+		Ok(())
+	}
+}
+
+// Game version as is
+pub struct VanillaLoader;
+
+impl ModLoader for VanillaLoader {
+	fn get_name(&self) -> &'static str {
+		"vanilla"
+	}
+
+	fn list_versions(&self, _game_version: &str) -> Result<Vec<String>, Error> {
+		Ok(Default::default())
+	}
+
+	fn install_profile(&self, game_version: &str) -> Result<String, Error> {
+		Ok(game_version.to_string())
+	}
+}
+
+// "fabric" or "fabric:0.15.7", no loader means vanilla
+// Forge and NeoForge also accept path to installer jar: "forge:forge-installer.jar"
+pub fn get(loader: Option<&str>) -> Result<Box<dyn ModLoader>, Error> {
+	let Some(loader) = loader else {
+		return Ok(Box::new(VanillaLoader));
+	};
+
+	let (name, loader_version) = match loader.split_once(':') {
+		Some((name, loader_version)) => (name, Some(loader_version.to_string())),
+		None => (loader, None),
+	};
+
+	match name {
+		"vanilla" => Ok(Box::new(VanillaLoader)),
+		"fabric" => Ok(Box::new(FabricLoader::new(&fabric::FABRIC, loader_version))),
+		"quilt" => Ok(Box::new(FabricLoader::new(&quilt::QUILT, loader_version))),
+		"forge" => Ok(Box::new(ForgeLoader::new(Flavour::Forge, loader_version))),
+		"neoforge" => Ok(Box::new(ForgeLoader::new(
+			Flavour::NeoForge,
+			loader_version,
+		))),
+		_ => Err(Error::Default(format!("unknown modloader: {name}"))),
+	}
+}
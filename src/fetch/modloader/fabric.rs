@@ -1,13 +1,44 @@
 use std::collections::HashMap;
+use std::fs;
 
 use serde::{Deserialize, Serialize};
 
 use crate::fetch::textfile::RetrievePlainText;
 
+use super::ModLoader;
+
 use crate::util::error::Error;
 
+/* FABRIC
+* Meta server gives us:
+* - /versions/loader/[game version]: loader builds, that support game version
+* - /versions/loader/[game version]/[loader version]/profile/json: version package,
+*   which inherits from vanilla one and adds loader libraries (maven name + repository url)
+* Profile is saved into data/versions, so it is launched like any other local version
+* Other loaders (quilt) have meta servers with the same api, so they are described by MetaServer
+*/
+
 const URL_FABRIC: &str = "https://meta.fabricmc.net/v2/versions/loader";
 
+pub const FABRIC: MetaServer = MetaServer {
+	name: "fabric",
+	url: URL_FABRIC,
+	reports_stability: true,
+};
+
+// Loader for any fabric-like meta server
+pub struct FabricLoader {
+	meta: &'static MetaServer,
+	version: Option<String>,
+}
+
+pub struct MetaServer {
+	pub name: &'static str,
+	pub url: &'static str,
+	// Without "stable" field, builds with suffix (0.20.0-beta.1) are counted as unstable
+	pub reports_stability: bool,
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct VersionManifest {
@@ -18,6 +49,13 @@ pub struct VersionManifest {
 	pub stable: bool,
 }
 
+// Entry of loaders list for certain game version
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GameLoader {
+	pub loader: VersionManifest,
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 #[serde(rename_all = "camelCase")]
@@ -44,19 +82,100 @@ pub struct Library {
 }
 
 impl VersionManifest {
-	pub fn new() -> Result<Self, Error> {
-		let path = String::from("data/fabric_manifest.json");
+	// Newest loaders go first
+	pub fn get_for_game(meta: &MetaServer, game_version: &str) -> Result<Vec<Self>, Error> {
+		let path = format!("data/{}/{game_version}/loaders.json", meta.name);
+
+		let loaders: Vec<GameLoader> = serde_json::from_str(
+			Self::retrieve_text(&path, &format!("{}/{game_version}", meta.url), None)?.as_str(),
+		)?;
+
+		Ok(loaders.into_iter().map(|entry| entry.loader).collect())
+	}
+
+	// Latest stable loader, if version wasn't given
+	pub fn select(
+		meta: &MetaServer,
+		game_version: &str,
+		loader_version: Option<&str>,
+	) -> Result<Self, Error> {
+		let loaders = Self::get_for_game(meta, game_version)?;
+
+		let loader = match loader_version {
+			Some(loader_version) => loaders
+				.into_iter()
+				.find(|loader| loader.version == loader_version),
+			None => {
+				let stable = loaders.iter().position(|loader| loader.is_stable(meta));
+				loaders.into_iter().nth(stable.unwrap_or(0))
+			}
+		};
+
+		loader.ok_or_else(|| {
+			Error::Default(format!(
+				"{} loader {} is not available for minecraft {game_version}",
+				meta.name,
+				loader_version.unwrap_or("(any)")
+			))
+		})
+	}
 
-		Ok(serde_json::from_str(
-			Self::retrieve_text(
-				&path,
-				&URL_FABRIC.to_string(),
-				None,
-			)?
-			.as_str(),
-		)?)
+	pub fn is_stable(&self, meta: &MetaServer) -> bool {
+		match meta.reports_stability {
+			true => self.stable,
+			false => !self.version.contains('-'),
+		}
 	}
 }
 impl RetrievePlainText for VersionManifest {}
 
+impl Version {
+	pub fn new(meta: &MetaServer, game_version: &str, loader_version: &str) -> Result<Self, Error> {
+		let id = format!("{}-loader-{loader_version}-{game_version}", meta.name);
+		let path = format!("data/versions/{id}/{id}.json");
+
+		let text = Self::retrieve_text(
+			&path,
+			&format!("{}/{game_version}/{loader_version}/profile/json", meta.url),
+			None,
+		)?;
+		let version: Self = serde_json::from_str(&text)?;
+
+		// Local versions are searched by their id
+		if version.id != id {
+			let path = format!("data/versions/{}/{}.json", version.id, version.id);
+			fs::create_dir_all(format!("data/versions/{}", version.id))?;
+			fs::write(path, &text)?;
+		}
+
+		Ok(version)
+	}
+}
 impl RetrievePlainText for Version {}
+
+impl FabricLoader {
+	// Latest stable loader, if version wasn't given
+	pub fn new(meta: &'static MetaServer, version: Option<String>) -> Self {
+		Self { meta, version }
+	}
+}
+
+impl ModLoader for FabricLoader {
+	fn get_name(&self) -> &'static str {
+		self.meta.name
+	}
+
+	fn list_versions(&self, game_version: &str) -> Result<Vec<String>, Error> {
+		Ok(VersionManifest::get_for_game(self.meta, game_version)?
+			.into_iter()
+			.map(|loader| loader.version)
+			.collect())
+	}
+
+	fn install_profile(&self, game_version: &str) -> Result<String, Error> {
+		let loader = VersionManifest::select(self.meta, game_version, self.version.as_deref())?;
+		println!("Using {} loader {}", self.meta.name, loader.version);
+
+		Ok(Version::new(self.meta, game_version, &loader.version)?.id)
+	}
+}
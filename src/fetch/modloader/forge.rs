@@ -0,0 +1,959 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use checksums::{hash_file, Algorithm};
+
+use zip::ZipArchive;
+
+use crate::fetch::maven::{self, Coordinate};
+use crate::fetch::minecraft::Minecraft;
+use crate::fetch::textfile::RetrievePlainText;
+use crate::fetch::vanilla::{DataObject, Library, Vanilla, OS};
+use crate::java::JavaRuntime;
+use crate::util::error::Error;
+
+use super::ModLoader;
+
+/* FORGE / NEOFORGE
+* Both are distributed as installer jar with "install_profile.json" inside:
+* - Since 1.13 (spec 1): "json" points to version package inside of installer,
+*   "libraries" are needed by "processors" - java programs, that patch client jar,
+*   split it and apply mappings. "data" contains values for processors arguments:
+*   '[maven name]' - library path, "'literal'", "/path" - file inside of installer
+*   Processors declare "outputs" with sha1 of files, they should produce
+*   Processors are run once per installer, it's hash is saved after they succeed
+* - Before 1.13: "versionInfo" is version package itself, "install" tells where
+*   to put universal jar from installer
+* Libraries, that are bundled into installer, are stored in it's "maven/" directory
+*/
+
+const URL_FORGE_MAVEN: &str = "https://maven.minecraftforge.net";
+const URL_FORGE_PROMOTIONS: &str =
+	"https://files.minecraftforge.net/net/minecraftforge/forge/promotions_slim.json";
+const URL_NEOFORGE_MAVEN: &str = "https://maven.neoforged.net/releases";
+
+// Legacy version packages don't have urls for mojang's libraries
+const URL_MOJANG_LIBRARIES: &str = "https://libraries.minecraft.net/";
+
+const DATA_DIR: &str = "data";
+const INSTALLERS_DIR: &str = "data/forge";
+// Inside of work directory, keeps sha1 of installer, which processors were run for
+const INSTALLED_MARKER: &str = ".installed";
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Flavour {
+	Forge,
+	NeoForge,
+}
+
+pub struct Installer {
+	flavour: Flavour,
+	path: PathBuf,
+	// Versions, libraries and work directories are placed inside of it
+	data_dir: PathBuf,
+	// Files from installer's "data", which are passed to processors
+	work_dir: PathBuf,
+	libraries_dir: PathBuf,
+}
+
+// Installer is resolved, when profile is installed, it's needed for later steps
+pub struct ForgeLoader {
+	flavour: Flavour,
+	version: Option<String>,
+	installer: OnceCell<Installer>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct InstallProfile {
+	spec: u32,
+	version: String,
+	minecraft: String,
+	json: String,
+	data: HashMap<String, SidedData>,
+	processors: Vec<Processor>,
+	libraries: Vec<Library>,
+	// Before 1.13
+	install: Option<LegacyInstall>,
+	version_info: Option<serde_json::Value>,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct SidedData {
+	client: String,
+	server: String,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct Processor {
+	sides: Option<Vec<String>>,
+	jar: String,
+	classpath: Vec<String>,
+	args: Vec<String>,
+	// [path] [sha1], both may be references to "data"
+	outputs: HashMap<String, String>,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct LegacyInstall {
+	path: String,
+	file_path: String,
+	target: String,
+	minecraft: String,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct Promotions {
+	promos: HashMap<String, String>,
+}
+
+impl Flavour {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Flavour::Forge => "forge",
+			Flavour::NeoForge => "neoforge",
+		}
+	}
+
+	pub fn get_repository(&self) -> &'static str {
+		match self {
+			Flavour::Forge => URL_FORGE_MAVEN,
+			Flavour::NeoForge => URL_NEOFORGE_MAVEN,
+		}
+	}
+}
+
+impl ForgeLoader {
+	pub fn new(flavour: Flavour, version: Option<String>) -> Self {
+		Self {
+			flavour,
+			version,
+			installer: OnceCell::new(),
+		}
+	}
+
+	fn get_installer(&self) -> Result<&Installer, Error> {
+		self.installer.get().ok_or_else(|| {
+			Error::Default(format!("{} profile isn't installed", self.flavour.name()))
+		})
+	}
+}
+
+impl ModLoader for ForgeLoader {
+	fn get_name(&self) -> &'static str {
+		self.flavour.name()
+	}
+
+	fn list_versions(&self, game_version: &str) -> Result<Vec<String>, Error> {
+		Installer::list_versions(self.flavour, game_version)
+	}
+
+	fn install_profile(&self, game_version: &str) -> Result<String, Error> {
+		let installer = Installer::new(self.flavour, game_version, self.version.as_deref())?;
+		let id = installer.install_profile()?;
+		let _ = self.installer.set(installer);
+
+		Ok(id)
+	}
+
+	fn get_data_objects(&self, _profile: &Vanilla) -> Result<Vec<DataObject>, Error> {
+		self.get_installer()?.get_data_objects()
+	}
+
+	fn install(&self, profile: &Vanilla, java: &JavaRuntime) -> Result<(), Error> {
+		self.get_installer()?
+			.run_processors(&profile.get_client_object()?, java)
+	}
+}
+
+impl Installer {
+	// Installer jar given by user is used as is, everything else is a loader version
+	pub fn new(flavour: Flavour, game_version: &str, loader: Option<&str>) -> Result<Self, Error> {
+		let path = match loader {
+			Some(loader) if loader.ends_with(".jar") || Path::new(loader).is_file() => {
+				PathBuf::from(loader)
+			}
+			_ => Self::download(flavour, game_version, loader)?,
+		};
+
+		Self::from_jar(flavour, path, Path::new(DATA_DIR))
+	}
+
+	// Installer, that is already on disk, works without network
+	pub fn from_jar(flavour: Flavour, path: PathBuf, data_dir: &Path) -> Result<Self, Error> {
+		let name = path
+			.file_stem()
+			.map(|stem| stem.to_string_lossy().into_owned())
+			.unwrap_or_else(|| flavour.name().to_string());
+
+		fs::create_dir_all(data_dir.join("libraries"))?;
+		let data_dir = fs::canonicalize(data_dir)?;
+
+		Ok(Self {
+			flavour,
+			work_dir: data_dir.join("forge").join(name),
+			libraries_dir: data_dir.join("libraries"),
+			data_dir,
+			path,
+		})
+	}
+
+	fn download(
+		flavour: Flavour,
+		game_version: &str,
+		loader: Option<&str>,
+	) -> Result<PathBuf, Error> {
+		let loader = match loader {
+			Some(loader) => loader.to_string(),
+			None => Self::get_latest(flavour, game_version)?,
+		};
+
+		let coordinate = match flavour {
+			// Forge versions are prefixed with game version: 1.20.1-47.2.0
+			Flavour::Forge => match loader.starts_with(&format!("{game_version}-")) {
+				true => format!("net.minecraftforge:forge:{loader}:installer"),
+				false => format!("net.minecraftforge:forge:{game_version}-{loader}:installer"),
+			},
+			Flavour::NeoForge => format!("net.neoforged:neoforge:{loader}:installer"),
+		};
+		let coordinate = Coordinate::parse(&coordinate)
+			.ok_or_else(|| Error::Default(format!("wrong {} version: {loader}", flavour.name())))?;
+
+		let name = coordinate.get_file_name();
+		let url = coordinate.get_url(flavour.get_repository());
+
+		let path = format!("{INSTALLERS_DIR}/{name}");
+		Minecraft::download_objects(&[DataObject {
+			path: path.clone(),
+			url,
+			..Default::default()
+		}])?;
+
+		Ok(PathBuf::from(path))
+	}
+
+	// Newest go first, forge versions are without game version prefix
+	pub fn list_versions(flavour: Flavour, game_version: &str) -> Result<Vec<String>, Error> {
+		let versions = match flavour {
+			Flavour::Forge => maven::get_versions(URL_FORGE_MAVEN, "net.minecraftforge", "forge")?
+				.into_iter()
+				.filter_map(|version| {
+					version
+						.strip_prefix(&format!("{game_version}-"))
+						.map(String::from)
+				})
+				.collect::<Vec<String>>(),
+			// 1.20.4 -> 20.4.x, 1.21 -> 21.0.x
+			Flavour::NeoForge => {
+				let short = game_version.strip_prefix("1.").unwrap_or(game_version);
+				let prefix = match short.contains('.') {
+					true => format!("{short}."),
+					false => format!("{short}.0."),
+				};
+
+				maven::get_versions(URL_NEOFORGE_MAVEN, "net.neoforged", "neoforge")?
+					.into_iter()
+					.filter(|version| version.starts_with(&prefix))
+					.collect()
+			}
+		};
+
+		Ok(versions.into_iter().rev().collect())
+	}
+
+	// Recommended forge build or newest neoforge build for game version
+	fn get_latest(flavour: Flavour, game_version: &str) -> Result<String, Error> {
+		match flavour {
+			Flavour::Forge => {
+				let promotions: Promotions = serde_json::from_str(&Self::retrieve_text(
+					&format!("{INSTALLERS_DIR}/promotions_slim.json"),
+					&URL_FORGE_PROMOTIONS.to_string(),
+					None,
+				)?)?;
+
+				promotions
+					.promos
+					.get(&format!("{game_version}-recommended"))
+					.or_else(|| promotions.promos.get(&format!("{game_version}-latest")))
+					.cloned()
+			}
+			Flavour::NeoForge => Self::list_versions(flavour, game_version)?
+				.into_iter()
+				.next(),
+		}
+		.ok_or_else(|| {
+			Error::Default(format!(
+				"no {} builds for minecraft {game_version}",
+				flavour.name()
+			))
+		})
+	}
+
+	// Returns id of installed version
+	pub fn install_profile(&self) -> Result<String, Error> {
+		println!(
+			"Installing {} from {}. . .",
+			self.flavour.name(),
+			self.path.display()
+		);
+
+		let (mut archive, mut profile) = self.open()?;
+
+		Self::extract_maven(&mut archive, &self.libraries_dir)?;
+
+		if let (Some(install), Some(version_info)) =
+			(profile.install.take(), profile.version_info.take())
+		{
+			return self.install_legacy(&mut archive, install, version_info);
+		}
+
+		let text = Self::read_entry(&mut archive, profile.json.trim_start_matches('/'))?;
+		let version: Vanilla = serde_json::from_str(&text)?;
+		let version_dir = self.data_dir.join("versions").join(&version.id);
+		fs::create_dir_all(&version_dir)?;
+		fs::write(version_dir.join(format!("{}.json", version.id)), &text)?;
+
+		Ok(version.id)
+	}
+
+	// Libraries of processors
+	pub fn get_data_objects(&self) -> Result<Vec<DataObject>, Error> {
+		let (_, profile) = self.open()?;
+
+		let host = OS::current();
+		Ok(profile
+			.libraries
+			.iter()
+			.filter(|library| library.is_allowed(&host))
+			.filter_map(|library| library.get_artifact())
+			.map(|artifact| {
+				let mut object = DataObject {
+					path: self
+						.libraries_dir
+						.join(&artifact.path)
+						.to_string_lossy()
+						.into_owned(),
+					..artifact
+				};
+				maven::fill_checksum(&mut object);
+				object
+			})
+			.collect())
+	}
+
+	// Client jar should be already downloaded
+	pub fn run_processors(&self, client: &DataObject, java: &JavaRuntime) -> Result<(), Error> {
+		let (mut archive, profile) = self.open()?;
+
+		let processors: Vec<&Processor> = profile
+			.processors
+			.iter()
+			.filter(|processor| {
+				processor
+					.sides
+					.as_ref()
+					.is_none_or(|sides| sides.iter().any(|side| side == "client"))
+			})
+			.collect();
+		if processors.is_empty() {
+			return Ok(());
+		}
+
+		let data = self.get_data(&mut archive, &profile, client)?;
+
+		// Processors without outputs (like DOWNLOAD_MOJMAPS) can't be checked,
+		// so finished installation is remembered instead
+		let marker = self.work_dir.join(INSTALLED_MARKER);
+		let installer_hash = hash_file(&self.path, Algorithm::SHA1);
+		if fs::read_to_string(&marker).is_ok_and(|hash| hash.trim() == installer_hash)
+			&& self.check_outputs(&processors, &data)?
+		{
+			return Ok(());
+		}
+
+		for processor in processors {
+			self.run_processor(processor, &data, java)?;
+		}
+
+		fs::create_dir_all(&self.work_dir)?;
+		fs::write(marker, installer_hash)?;
+
+		Ok(())
+	}
+
+	// Patched client may be removed or broken after installation
+	fn check_outputs(
+		&self,
+		processors: &[&Processor],
+		data: &HashMap<String, String>,
+	) -> Result<bool, Error> {
+		for processor in processors {
+			for (path, hash) in self.get_outputs(processor, data)? {
+				if !Self::check_hash(&path, &hash) {
+					return Ok(false);
+				}
+			}
+		}
+
+		Ok(true)
+	}
+
+	fn get_outputs(
+		&self,
+		processor: &Processor,
+		data: &HashMap<String, String>,
+	) -> Result<Vec<(String, String)>, Error> {
+		processor
+			.outputs
+			.iter()
+			.map(|(path, hash)| Ok((self.resolve(path, data)?, self.resolve(hash, data)?)))
+			.collect()
+	}
+
+	fn open(&self) -> Result<(ZipArchive<fs::File>, InstallProfile), Error> {
+		let mut archive = ZipArchive::new(fs::File::open(&self.path)?)?;
+		let profile =
+			serde_json::from_str(&Self::read_entry(&mut archive, "install_profile.json")?)?;
+
+		Ok((archive, profile))
+	}
+
+	fn install_legacy(
+		&self,
+		archive: &mut ZipArchive<fs::File>,
+		install: LegacyInstall,
+		mut version_info: serde_json::Value,
+	) -> Result<String, Error> {
+		// Universal jar is not available in maven under plain name
+		let target = self.get_library_path(&install.path)?;
+		Self::extract_entry(archive, &install.file_path, &target)?;
+
+		if let Some(libraries) = version_info["libraries"].as_array_mut() {
+			for library in libraries {
+				let Some(library) = library.as_object_mut() else {
+					continue;
+				};
+				if library.get("name").and_then(|name| name.as_str()) == Some(&install.path) {
+					library.insert("url".into(), "".into());
+				} else if !library.contains_key("url") && !library.contains_key("downloads") {
+					library.insert("url".into(), URL_MOJANG_LIBRARIES.into());
+				}
+			}
+		}
+
+		if version_info.get("inheritsFrom").is_none() {
+			version_info["inheritsFrom"] = install.minecraft.clone().into();
+		}
+
+		let id = match version_info["id"].as_str() {
+			Some(id) => id.to_string(),
+			None => install.target.clone(),
+		};
+		let version_dir = self.data_dir.join("versions").join(&id);
+		fs::create_dir_all(&version_dir)?;
+		fs::write(
+			version_dir.join(format!("{id}.json")),
+			serde_json::to_string_pretty(&version_info)?,
+		)?;
+
+		Ok(id)
+	}
+
+	// Values for {KEY} references in processors arguments
+	fn get_data(
+		&self,
+		archive: &mut ZipArchive<fs::File>,
+		profile: &InstallProfile,
+		client: &DataObject,
+	) -> Result<HashMap<String, String>, Error> {
+		let mut data: HashMap<String, String> = HashMap::from([
+			(String::from("SIDE"), String::from("client")),
+			(
+				String::from("MINECRAFT_JAR"),
+				fs::canonicalize(&client.path)?
+					.to_string_lossy()
+					.into_owned(),
+			),
+			(String::from("MINECRAFT_VERSION"), profile.minecraft.clone()),
+			(
+				String::from("ROOT"),
+				self.data_dir.to_string_lossy().into_owned(),
+			),
+			(
+				String::from("INSTALLER"),
+				fs::canonicalize(&self.path)?.to_string_lossy().into_owned(),
+			),
+			(
+				String::from("LIBRARY_DIR"),
+				self.libraries_dir.to_string_lossy().into_owned(),
+			),
+		]);
+
+		for (key, value) in &profile.data {
+			let value = &value.client;
+
+			let resolved = if let Some(file) = value.strip_prefix('/') {
+				let target = self.work_dir.join(file);
+				Self::extract_entry(archive, file, &target)?;
+				fs::canonicalize(target)?.to_string_lossy().into_owned()
+			} else {
+				self.resolve(value, &Default::default())?
+			};
+
+			data.insert(key.clone(), resolved);
+		}
+
+		Ok(data)
+	}
+
+	// {KEY} -> data value, [maven name] -> library path, 'literal' -> literal
+	fn resolve(&self, value: &str, data: &HashMap<String, String>) -> Result<String, Error> {
+		if let Some(key) = value
+			.strip_prefix('{')
+			.and_then(|value| value.strip_suffix('}'))
+		{
+			return data
+				.get(key)
+				.cloned()
+				.ok_or_else(|| Error::Default(format!("unknown installer data: {key}")));
+		}
+
+		if let Some(name) = value
+			.strip_prefix('[')
+			.and_then(|value| value.strip_suffix(']'))
+		{
+			return Ok(self.get_library_path(name)?.to_string_lossy().into_owned());
+		}
+
+		if let Some(literal) = value
+			.strip_prefix('\'')
+			.and_then(|value| value.strip_suffix('\''))
+		{
+			return Ok(literal.to_string());
+		}
+
+		Ok(value.to_string())
+	}
+
+	fn get_library_path(&self, name: &str) -> Result<PathBuf, Error> {
+		match Coordinate::parse(name) {
+			Some(coordinate) => Ok(self.libraries_dir.join(coordinate.get_path())),
+			None => Err(Error::Default(format!("wrong library name: {name}"))),
+		}
+	}
+
+	fn run_processor(
+		&self,
+		processor: &Processor,
+		data: &HashMap<String, String>,
+		java: &JavaRuntime,
+	) -> Result<(), Error> {
+		let outputs = self.get_outputs(processor, data)?;
+
+		// Already done during previous installation
+		if !outputs.is_empty()
+			&& outputs
+				.iter()
+				.all(|(path, hash)| Self::check_hash(path, hash))
+		{
+			return Ok(());
+		}
+
+		let jar = self.get_library_path(&processor.jar)?;
+		let main_class = Self::get_main_class(&jar)?;
+
+		let mut class_path = vec![jar];
+		for name in &processor.classpath {
+			class_path.push(self.get_library_path(name)?);
+		}
+		let class_path = std::env::join_paths(class_path)
+			.map_err(|e| Error::Default(format!("processor class path: {e}")))?;
+
+		let arguments = processor
+			.args
+			.iter()
+			.map(|argument| self.resolve(argument, data))
+			.collect::<Result<Vec<String>, Error>>()?;
+
+		println!("Running processor {}. . .", processor.jar);
+		let status = Command::new(&java.executable)
+			.arg("-cp")
+			.arg(class_path)
+			.arg(&main_class)
+			.args(arguments)
+			.status()?;
+
+		if !status.success() {
+			return Err(Error::Default(format!(
+				"processor {} failed with {status}",
+				processor.jar
+			)));
+		}
+
+		for (path, hash) in &outputs {
+			if !Self::check_hash(path, hash) {
+				return Err(Error::Default(format!(
+					"processor {} produced {path} with wrong hash",
+					processor.jar
+				)));
+			}
+		}
+
+		Ok(())
+	}
+
+	fn check_hash(path: &str, hash: &str) -> bool {
+		let path = Path::new(path);
+		path.exists() && hash_file(path, Algorithm::SHA1).eq_ignore_ascii_case(hash)
+	}
+
+	fn get_main_class(jar: &Path) -> Result<String, Error> {
+		let mut archive = ZipArchive::new(fs::File::open(jar)?)?;
+		let manifest = Self::read_entry(&mut archive, "META-INF/MANIFEST.MF")?;
+
+		manifest
+			.lines()
+			.find_map(|line| line.strip_prefix("Main-Class:"))
+			.map(|main_class| main_class.trim().to_string())
+			.ok_or_else(|| Error::Default(format!("{} has no main class", jar.display())))
+	}
+
+	fn read_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<String, Error> {
+		let mut text = String::new();
+		archive.by_name(name)?.read_to_string(&mut text)?;
+
+		Ok(text)
+	}
+
+	fn extract_entry(
+		archive: &mut ZipArchive<fs::File>,
+		name: &str,
+		target: &Path,
+	) -> Result<(), Error> {
+		if let Some(parent) = target.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		io::copy(&mut archive.by_name(name)?, &mut fs::File::create(target)?)?;
+
+		Ok(())
+	}
+
+	// Libraries bundled into installer
+	fn extract_maven(
+		archive: &mut ZipArchive<fs::File>,
+		libraries_dir: &Path,
+	) -> Result<(), Error> {
+		for index in 0..archive.len() {
+			let mut entry = archive.by_index(index)?;
+			if entry.is_dir() {
+				continue;
+			}
+			let Some(name) = entry.enclosed_name() else {
+				continue;
+			};
+			let Ok(relative) = name.strip_prefix("maven") else {
+				continue;
+			};
+
+			let target = libraries_dir.join(relative);
+			if let Some(parent) = target.parent() {
+				fs::create_dir_all(parent)?;
+			}
+			io::copy(&mut entry, &mut fs::File::create(target)?)?;
+		}
+
+		Ok(())
+	}
+}
+impl RetrievePlainText for Installer {}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use checksums::hash_reader;
+	use serde_json::json;
+	use zip::write::SimpleFileOptions;
+	use zip::ZipWriter;
+
+	use super::*;
+	use crate::util::testing::get_temp_dir;
+
+	const PATCHED: &[u8] = b"patched client";
+	const BINPATCH: &[u8] = b"binary patches";
+
+	fn sha1(bytes: &[u8]) -> String {
+		hash_reader(&mut &bytes[..], Algorithm::SHA1).to_lowercase()
+	}
+
+	fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+		let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+		for (name, bytes) in entries {
+			zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+			zip.write_all(bytes).unwrap();
+		}
+		zip.finish().unwrap();
+	}
+
+	// Installer with one client processor, which writes patched client into {PATCHED}
+	fn write_installer(dir: &Path, patched_hash: &str) -> PathBuf {
+		let processor = dir.join("processor.jar");
+		write_zip(
+			&processor,
+			&[(
+				"META-INF/MANIFEST.MF",
+				b"Manifest-Version: 1.0\nMain-Class: test.Patcher\n",
+			)],
+		);
+
+		let profile = json!({
+			"spec": 1,
+			"version": "1.20.1-forge-47.2.0",
+			"minecraft": "1.20.1",
+			"json": "/version.json",
+			"data": {
+				"PATCHED": { "client": "[net.minecraftforge:forge:1.20.1-47.2.0:client]", "server": "" },
+				"PATCHED_SHA": { "client": format!("'{patched_hash}'"), "server": "" },
+				"BINPATCH": { "client": "/data/client.lzma", "server": "" },
+				"MAPPINGS": { "client": "plain", "server": "" }
+			},
+			"processors": [
+				{
+					"jar": "test:patcher:1.0",
+					"args": ["--input", "{MINECRAFT_JAR}", "--patch", "{BINPATCH}", "--output", "{PATCHED}"],
+					"outputs": { "{PATCHED}": "{PATCHED_SHA}" }
+				},
+				// Would fail, if it was run: its jar doesn't exist
+				{ "sides": ["server"], "jar": "test:server:1.0", "args": [] }
+			]
+		});
+		let version = json!({
+			"id": "1.20.1-forge-47.2.0",
+			"inheritsFrom": "1.20.1",
+			"mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher"
+		});
+
+		let installer = dir.join("forge-1.20.1-47.2.0-installer.jar");
+		write_zip(
+			&installer,
+			&[
+				("install_profile.json", profile.to_string().as_bytes()),
+				("version.json", version.to_string().as_bytes()),
+				("data/client.lzma", BINPATCH),
+				(
+					"maven/test/patcher/1.0/patcher-1.0.jar",
+					&fs::read(processor).unwrap(),
+				),
+			],
+		);
+		installer
+	}
+
+	// Stand-in of java: remembers arguments and writes patched client after "--output"
+	#[cfg(unix)]
+	fn write_java(dir: &Path) -> JavaRuntime {
+		use std::os::unix::fs::PermissionsExt;
+
+		let executable = dir.join("java");
+		fs::write(
+			&executable,
+			format!(
+				"#!/bin/sh\necho \"$@\" >> '{}'\nwhile [ $# -gt 0 ]; do\n\tif [ \"$1\" = --output ]; then mkdir -p \"$(dirname \"$2\")\"; printf '{}' > \"$2\"; fi\n\tshift\ndone\n",
+				dir.join("runs.log").display(),
+				String::from_utf8_lossy(PATCHED)
+			),
+		)
+		.unwrap();
+		fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+
+		JavaRuntime {
+			executable,
+			major_version: 17,
+		}
+	}
+
+	fn get_client(dir: &Path) -> DataObject {
+		let path = dir.join("client.jar");
+		fs::write(&path, b"vanilla client").unwrap();
+		DataObject {
+			path: path.to_string_lossy().into_owned(),
+			..Default::default()
+		}
+	}
+
+	fn install(name: &str, patched_hash: &str) -> (PathBuf, Installer) {
+		let dir = get_temp_dir(name);
+		let installer = write_installer(&dir, patched_hash);
+		let installer = Installer::from_jar(Flavour::Forge, installer, &dir.join("data")).unwrap();
+		assert_eq!(installer.install_profile().unwrap(), "1.20.1-forge-47.2.0");
+
+		(dir, installer)
+	}
+
+	fn read_runs(dir: &Path) -> Vec<String> {
+		fs::read_to_string(dir.join("runs.log"))
+			.unwrap_or_default()
+			.lines()
+			.map(String::from)
+			.collect()
+	}
+
+	#[test]
+	fn installs_profile_and_bundled_libraries() {
+		let (dir, installer) = install("forge-profile", &sha1(PATCHED));
+		let data_dir = dir.join("data");
+
+		let version = data_dir.join("versions/1.20.1-forge-47.2.0/1.20.1-forge-47.2.0.json");
+		let version: Vanilla = serde_json::from_str(&fs::read_to_string(version).unwrap()).unwrap();
+		assert_eq!(version.inherits_from.as_deref(), Some("1.20.1"));
+		assert!(data_dir
+			.join("libraries/test/patcher/1.0/patcher-1.0.jar")
+			.exists());
+		assert_eq!(
+			Installer::get_main_class(&installer.get_library_path("test:patcher:1.0").unwrap())
+				.unwrap(),
+			"test.Patcher"
+		);
+	}
+
+	#[test]
+	fn resolves_data() {
+		let (dir, installer) = install("forge-data", &sha1(PATCHED));
+		let client = get_client(&dir);
+		let data_dir = fs::canonicalize(dir.join("data")).unwrap();
+
+		let (mut archive, profile) = installer.open().unwrap();
+		let data = installer.get_data(&mut archive, &profile, &client).unwrap();
+
+		// [maven coordinate]
+		assert_eq!(
+			PathBuf::from(&data["PATCHED"]),
+			data_dir.join(
+				"libraries/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-client.jar"
+			)
+		);
+		// 'literal'
+		assert_eq!(data["PATCHED_SHA"], sha1(PATCHED));
+		// /path inside of installer is extracted into work directory
+		assert!(data["BINPATCH"].starts_with(&*installer.work_dir.to_string_lossy()));
+		assert_eq!(fs::read(&data["BINPATCH"]).unwrap(), BINPATCH);
+		assert_eq!(data["MAPPINGS"], "plain");
+		// Values of launcher
+		assert_eq!(data["SIDE"], "client");
+		assert_eq!(data["MINECRAFT_VERSION"], "1.20.1");
+		assert_eq!(PathBuf::from(&data["ROOT"]), data_dir);
+
+		// {KEY}
+		assert_eq!(installer.resolve("{SIDE}", &data).unwrap(), "client");
+		assert!(installer.resolve("{UNKNOWN}", &data).is_err());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn runs_processors_once() {
+		let (dir, installer) = install("forge-processors", &sha1(PATCHED));
+		let java = write_java(&dir);
+		let client = get_client(&dir);
+
+		installer.run_processors(&client, &java).unwrap();
+
+		let runs = read_runs(&dir);
+		assert_eq!(runs.len(), 1);
+		assert!(runs[0].starts_with("-cp "));
+		assert!(runs[0].contains(" test.Patcher --input "));
+		let patched = installer
+			.get_library_path("net.minecraftforge:forge:1.20.1-47.2.0:client")
+			.unwrap();
+		assert!(runs[0].ends_with(&format!("--output {}", patched.display())));
+		assert_eq!(fs::read(&patched).unwrap(), PATCHED);
+		assert_eq!(
+			fs::read_to_string(installer.work_dir.join(INSTALLED_MARKER)).unwrap(),
+			hash_file(&installer.path, Algorithm::SHA1)
+		);
+
+		// Marker skips finished installation
+		installer.run_processors(&client, &java).unwrap();
+		assert_eq!(read_runs(&dir).len(), 1);
+
+		// Unless the output is gone
+		fs::remove_file(&patched).unwrap();
+		installer.run_processors(&client, &java).unwrap();
+		assert_eq!(read_runs(&dir).len(), 2);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn wrong_output_hash_fails() {
+		let (dir, installer) = install("forge-hash", &sha1(b"something else"));
+		let java = write_java(&dir);
+
+		let error = installer
+			.run_processors(&get_client(&dir), &java)
+			.unwrap_err();
+		assert!(error.to_string().contains("wrong hash"));
+		assert!(!installer.work_dir.join(INSTALLED_MARKER).exists());
+	}
+
+	#[test]
+	fn installs_legacy_version_info() {
+		let dir = get_temp_dir("forge-legacy");
+		let forge = "net.minecraftforge:forge:1.7.10-10.13.4.1614-1.7.10";
+		let profile = json!({
+			"install": {
+				"path": forge,
+				"filePath": "forge-1.7.10-10.13.4.1614-1.7.10-universal.jar",
+				"target": "1.7.10-Forge10.13.4.1614-1.7.10",
+				"minecraft": "1.7.10"
+			},
+			"versionInfo": {
+				"id": "1.7.10-Forge10.13.4.1614-1.7.10",
+				"mainClass": "net.minecraft.launchwrapper.Launch",
+				"libraries": [
+					{ "name": forge },
+					{ "name": "net.minecraft:launchwrapper:1.12" },
+					{ "name": "org.scala-lang:scala-library:2.11.1", "url": "https://maven.minecraftforge.net/" }
+				]
+			}
+		});
+		let path = dir.join("forge-1.7.10-installer.jar");
+		write_zip(
+			&path,
+			&[
+				("install_profile.json", profile.to_string().as_bytes()),
+				(
+					"forge-1.7.10-10.13.4.1614-1.7.10-universal.jar",
+					b"universal",
+				),
+			],
+		);
+
+		let installer = Installer::from_jar(Flavour::Forge, path, &dir.join("data")).unwrap();
+		let id = installer.install_profile().unwrap();
+		assert_eq!(id, "1.7.10-Forge10.13.4.1614-1.7.10");
+
+		assert_eq!(
+			fs::read(installer.get_library_path(forge).unwrap()).unwrap(),
+			b"universal"
+		);
+
+		let version: serde_json::Value = serde_json::from_str(
+			&fs::read_to_string(dir.join(format!("data/versions/{id}/{id}.json"))).unwrap(),
+		)
+		.unwrap();
+		assert_eq!(version["inheritsFrom"], "1.7.10");
+		// Universal jar is already in place, others are downloaded from their repositories
+		assert_eq!(version["libraries"][0]["url"], "");
+		assert_eq!(version["libraries"][1]["url"], URL_MOJANG_LIBRARIES);
+		assert_eq!(
+			version["libraries"][2]["url"],
+			"https://maven.minecraftforge.net/"
+		);
+	}
+}
@@ -0,0 +1,15 @@
+use super::fabric::MetaServer;
+
+/* QUILT
+* Quilt meta server mirrors fabric's loader and profile endpoints,
+* so everything except of url is shared with fabric
+* It doesn't mark builds as stable, prereleases are told apart by version suffix
+*/
+
+const URL_QUILT: &str = "https://meta.quiltmc.org/v3/versions/loader";
+
+pub const QUILT: MetaServer = MetaServer {
+	name: "quilt",
+	url: URL_QUILT,
+	reports_stability: false,
+};
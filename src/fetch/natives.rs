@@ -0,0 +1,212 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use zip::ZipArchive;
+
+use crate::util::error::Error;
+
+use super::vanilla::{DataObject, Library, OS};
+
+/* NATIVES
+* Minecraft ships native libraries (lwjgl, openal, glfw...) in two ways:
+* - Before 1.19: library has "natives" map ([OS name] -> [classifier]) and jar
+*   is stored in "downloads.classifiers". "extract.exclude" lists paths that
+*   should be skipped (usually META-INF/)
+* - Since 1.19: natives are plain artifacts with classifier in their name
+*   (org.lwjgl:lwjgl:3.3.1:natives-linux), filtered out only by rules
+* Everything goes into versions/[id]/natives, and list of extracted jars is saved
+* next to them, so we don't unzip the same things on every launch
+*/
+
+const RECORD_FILE: &str = ".natives.json";
+
+// Jar signatures are useless outside of jar and only pollute natives directory
+const DEFAULT_EXCLUDE: &str = "META-INF/";
+
+const SHARED_LIBRARY_EXTENSIONS: [&str; 4] = ["so", "dll", "dylib", "jnilib"];
+
+pub struct Natives {
+	libraries_dir: PathBuf,
+	target: PathBuf,
+}
+
+#[derive(Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct Record {
+	jars: Vec<RecordEntry>,
+}
+#[derive(Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct RecordEntry {
+	path: String,
+	#[serde(rename = "sha1")]
+	hash: String,
+	// Extracted files, relative to natives directory. Not a part of comparison
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	files: Vec<String>,
+}
+
+struct Job<'a> {
+	object: &'a DataObject,
+	exclude: Vec<&'a str>,
+	// New style natives keep libraries under [os]/[arch]/... inside jar
+	flatten: bool,
+}
+
+impl Natives {
+	pub fn new(libraries_dir: impl Into<PathBuf>, target: impl Into<PathBuf>) -> Self {
+		Self {
+			libraries_dir: libraries_dir.into(),
+			target: target.into(),
+		}
+	}
+
+	pub fn extract(&self, libraries: &[Library]) -> Result<(), Error> {
+		let jobs = Self::collect_jobs(libraries);
+
+		let mut record = Record {
+			jars: jobs
+				.iter()
+				.map(|job| RecordEntry {
+					path: job.object.path.clone(),
+					hash: job.object.hash.to_lowercase(),
+					files: Default::default(),
+				})
+				.collect(),
+		};
+
+		if self.is_up_to_date(&record) {
+			return Ok(());
+		}
+
+		// Leftovers from other library versions may be loaded instead of the right ones
+		if self.target.exists() {
+			fs::remove_dir_all(&self.target)?;
+		}
+		fs::create_dir_all(&self.target)?;
+
+		for (job, entry) in jobs.iter().zip(record.jars.iter_mut()) {
+			entry.files = self.extract_jar(job)?;
+		}
+
+		fs::write(
+			self.target.join(RECORD_FILE),
+			serde_json::to_string_pretty(&record)?,
+		)?;
+
+		Ok(())
+	}
+
+	fn collect_jobs(libraries: &[Library]) -> Vec<Job<'_>> {
+		let host = OS::current();
+
+		libraries
+			.iter()
+			.filter(|&library| library.is_allowed(&host))
+			.filter_map(|library| {
+				if let Some(object) = library.get_native_classifier() {
+					let mut exclude: Vec<&str> = library
+						.extract
+						.as_ref()
+						.map(|extract| extract.exclude.iter().map(String::as_str).collect())
+						.unwrap_or_default();
+					exclude.push(DEFAULT_EXCLUDE);
+
+					return Some(Job {
+						object,
+						exclude,
+						flatten: false,
+					});
+				}
+
+				if library.is_native_artifact() {
+					return Some(Job {
+						object: library.downloads.artifact.as_ref()?,
+						exclude: vec![DEFAULT_EXCLUDE],
+						flatten: true,
+					});
+				}
+
+				None
+			})
+			.collect()
+	}
+
+	fn is_up_to_date(&self, record: &Record) -> bool {
+		let Ok(text) = fs::read_to_string(self.target.join(RECORD_FILE)) else {
+			return false;
+		};
+		let Ok(saved) = serde_json::from_str::<Record>(&text) else {
+			return false;
+		};
+
+		saved.jars.len() == record.jars.len()
+			&& saved
+				.jars
+				.iter()
+				.zip(record.jars.iter())
+				.all(|(saved, current)| {
+					saved.path == current.path
+						&& saved.hash == current.hash
+						&& saved
+							.files
+							.iter()
+							.all(|file| self.target.join(file).exists())
+				})
+	}
+
+	fn extract_jar(&self, job: &Job) -> Result<Vec<String>, Error> {
+		let path = self.libraries_dir.join(&job.object.path);
+		let mut archive = ZipArchive::new(fs::File::open(&path)?)?;
+		let mut extracted: Vec<String> = Default::default();
+
+		for index in 0..archive.len() {
+			let mut entry = archive.by_index(index)?;
+			if entry.is_dir() {
+				continue;
+			}
+			// Protection from "../" inside of entry names
+			let Some(name) = entry.enclosed_name() else {
+				continue;
+			};
+			if job
+				.exclude
+				.iter()
+				.any(|exclude| entry.name().starts_with(exclude))
+			{
+				continue;
+			}
+
+			let relative = if job.flatten {
+				if !Self::is_shared_library(&name) {
+					continue;
+				}
+				match name.file_name() {
+					Some(file_name) => PathBuf::from(file_name),
+					None => continue,
+				}
+			} else {
+				name
+			};
+
+			let output = self.target.join(&relative);
+			if let Some(parent) = output.parent() {
+				fs::create_dir_all(parent)?;
+			}
+			io::copy(&mut entry, &mut fs::File::create(&output)?)?;
+
+			extracted.push(relative.to_string_lossy().into_owned());
+		}
+
+		Ok(extracted)
+	}
+
+	fn is_shared_library(path: &Path) -> bool {
+		path.extension()
+			.and_then(|extension| extension.to_str())
+			.is_some_and(|extension| SHARED_LIBRARY_EXTENSIONS.contains(&extension))
+	}
+}
@@ -0,0 +1,414 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::error::Error;
+
+use super::minecraft::Minecraft;
+use super::textfile::RetrievePlainText;
+use super::vanilla::{DataObject, OSArch, OSName};
+
+/* JAVA RUNTIME
+* Official launcher doesn't rely on system java. Version package has "javaVersion.component"
+* (java-runtime-gamma, jre-legacy...), which is resolved through java-runtime manifest:
+* - [platform] [component] -> list of builds with url to build manifest
+* Build manifest is a list of files:
+* - "directory"
+* - "file": with download url, sha1 and executable bit
+* - "link": symlink with relative "target"
+* Runtime goes into data/runtime/[component]/[platform]/[component]
+*/
+
+const URL_JAVA_RUNTIMES: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+// Saved next to installed runtime, so files are rechecked only after update
+const VERSION_FILE: &str = ".version";
+
+pub struct RuntimeInstaller {
+	manifest_url: String,
+	root: PathBuf,
+}
+
+// [platform] [component] [builds]
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimesManifest {
+	#[serde(flatten)]
+	pub platforms: HashMap<String, HashMap<String, Vec<RuntimeBuild>>>,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeBuild {
+	pub manifest: DataObject,
+	pub version: RuntimeVersion,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeVersion {
+	pub name: String,
+	pub released: String,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeFiles {
+	pub files: HashMap<String, RuntimeFile>,
+}
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeFile {
+	pub r#type: RuntimeFileType,
+	pub executable: bool,
+	pub downloads: HashMap<String, DataObject>,
+	pub target: Option<String>,
+}
+#[derive(Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeFileType {
+	#[default]
+	File,
+	Directory,
+	Link,
+}
+
+impl RuntimeInstaller {
+	pub fn new(data_dir: &str) -> Self {
+		Self::with_manifest_url(data_dir, URL_JAVA_RUNTIMES)
+	}
+
+	// Mirrors and local stand-ins of Mojang's servers
+	pub fn with_manifest_url(data_dir: &str, manifest_url: &str) -> Self {
+		Self {
+			manifest_url: manifest_url.to_string(),
+			root: PathBuf::from(format!("{data_dir}/runtime")),
+		}
+	}
+
+	// Platform naming of java-runtime manifest
+	pub fn get_platform() -> Option<&'static str> {
+		match (OSName::current(), OSArch::current()) {
+			(OSName::Linux, OSArch::X86_64) => Some("linux"),
+			(OSName::Linux, OSArch::X86) => Some("linux-i386"),
+			(OSName::Osx, OSArch::X86_64) => Some("mac-os"),
+			(OSName::Osx, OSArch::Arm64) => Some("mac-os-arm64"),
+			(OSName::Windows, OSArch::X86_64) => Some("windows-x64"),
+			(OSName::Windows, OSArch::X86) => Some("windows-x86"),
+			(OSName::Windows, OSArch::Arm64) => Some("windows-arm64"),
+			_ => None,
+		}
+	}
+
+	pub fn get_home(&self, component: &str) -> Option<PathBuf> {
+		let home = self
+			.root
+			.join(component)
+			.join(Self::get_platform()?)
+			.join(component);
+
+		// Macos runtimes are bundles
+		let bundle = home.join("jre.bundle/Contents/Home");
+		match bundle.is_dir() {
+			true => Some(bundle),
+			false => Some(home),
+		}
+	}
+
+	// Returns java home of installed runtime
+	pub fn install(&self, component: &str) -> Result<PathBuf, Error> {
+		let platform = Self::get_platform().ok_or_else(|| {
+			Error::Default(format!(
+				"no managed java runtimes for {}-{}",
+				OSName::current().as_str(),
+				OSArch::current().as_str()
+			))
+		})?;
+
+		let manifest: RuntimesManifest = serde_json::from_str(&Self::retrieve_text(
+			&self.root.join("all.json").to_string_lossy().into_owned(),
+			&self.manifest_url,
+			None,
+		)?)?;
+
+		let build = manifest
+			.platforms
+			.get(platform)
+			.and_then(|components| components.get(component))
+			.and_then(|builds| builds.first())
+			.ok_or_else(|| {
+				Error::Default(format!(
+					"java runtime {component} is not available for {platform}"
+				))
+			})?;
+
+		let target = self.root.join(component).join(platform).join(component);
+		let version_path = self.root.join(component).join(platform).join(VERSION_FILE);
+
+		let installed = fs::read_to_string(&version_path).unwrap_or_default();
+		if installed.trim() != build.manifest.hash.as_ref() || !target.exists() {
+			println!(
+				"Installing java runtime {component} {}. . .",
+				build.version.name
+			);
+
+			let files: RuntimeFiles = serde_json::from_str(&Self::retrieve_text(
+				&self
+					.root
+					.join(component)
+					.join(platform)
+					.join(format!("{component}.json"))
+					.to_string_lossy()
+					.into_owned(),
+				&build.manifest.url,
+				Some(&build.manifest.hash.to_string()),
+			)?)?;
+
+			Self::install_files(&target, &files)?;
+			fs::write(&version_path, build.manifest.hash.as_ref())?;
+		}
+
+		self.get_home(component).ok_or_else(|| {
+			Error::Default(format!("java runtime {component} has no home directory"))
+		})
+	}
+
+	fn install_files(target: &Path, files: &RuntimeFiles) -> Result<(), Error> {
+		let mut objects: Vec<DataObject> = Default::default();
+
+		for (name, file) in &files.files {
+			let path = target.join(name);
+
+			match file.r#type {
+				RuntimeFileType::Directory => fs::create_dir_all(&path)?,
+				RuntimeFileType::File => {
+					let raw = file.downloads.get("raw").ok_or_else(|| {
+						Error::Default(format!("java runtime file {name} has no raw download"))
+					})?;
+					objects.push(DataObject {
+						path: path.to_string_lossy().into_owned(),
+						..raw.clone()
+					});
+				}
+				// Links are created after files, they may point to them
+				RuntimeFileType::Link => {}
+			}
+		}
+
+		Minecraft::download_objects(&objects)?;
+
+		for (name, file) in &files.files {
+			let path = target.join(name);
+
+			match file.r#type {
+				RuntimeFileType::File if file.executable => Self::set_executable(&path)?,
+				RuntimeFileType::Link => {
+					if let Some(link_target) = file.target.as_ref() {
+						Self::create_link(link_target, &path)?;
+					}
+				}
+				_ => {}
+			}
+		}
+
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	fn set_executable(path: &Path) -> Result<(), Error> {
+		use std::os::unix::fs::PermissionsExt;
+
+		fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+		Ok(())
+	}
+	#[cfg(not(unix))]
+	fn set_executable(_path: &Path) -> Result<(), Error> {
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	fn create_link(target: &str, path: &Path) -> Result<(), Error> {
+		if path.symlink_metadata().is_ok() {
+			fs::remove_file(path)?;
+		}
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		std::os::unix::fs::symlink(target, path)?;
+		Ok(())
+	}
+	// Windows runtimes don't have links
+	#[cfg(not(unix))]
+	fn create_link(_target: &str, _path: &Path) -> Result<(), Error> {
+		Ok(())
+	}
+}
+impl RetrievePlainText for RuntimeInstaller {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use checksums::{hash_reader, Algorithm};
+	use serde_json::json;
+
+	use crate::util::testing::{get_temp_dir, Response, TestServer};
+
+	const JAVA: &[u8] = b"#!/bin/sh\necho java\n";
+	const LIBRARY: &[u8] = b"not really a library";
+
+	fn sha1(bytes: &[u8]) -> String {
+		hash_reader(&mut &bytes[..], Algorithm::SHA1).to_lowercase()
+	}
+
+	// Runtime for current platform and decoy for another one
+	fn start_server(java: &'static [u8], platform: &'static str) -> TestServer {
+		let files = |url: &str| {
+			json!({
+				"files": {
+					"bin": { "type": "directory" },
+					"bin/java": {
+						"type": "file",
+						"executable": true,
+						"downloads": { "raw": { "sha1": sha1(JAVA), "size": JAVA.len(), "url": format!("{url}/bin/java") } }
+					},
+					"lib/libjli.so": {
+						"type": "file",
+						"executable": false,
+						"downloads": { "raw": { "sha1": sha1(LIBRARY), "size": LIBRARY.len(), "url": format!("{url}/lib/libjli.so") } }
+					},
+					"bin/jre-java": { "type": "link", "target": "java" }
+				}
+			})
+			.to_string()
+		};
+
+		TestServer::start(move |request| {
+			// Server doesn't know own url inside of handler, so it's taken from Host
+			let host = request
+				.headers
+				.iter()
+				.find(|(name, _)| name == "host")
+				.map(|(_, value)| format!("http://{value}"))
+				.unwrap_or_default();
+			let build = |path: &str| {
+				let manifest = files(&host);
+				json!([{
+					"manifest": { "sha1": sha1(manifest.as_bytes()), "size": manifest.len(), "url": format!("{host}{path}") },
+					"version": { "name": "17.0.8", "released": "2023-07-18T00:00:00+00:00" }
+				}])
+			};
+
+			match request.path.as_str() {
+				"/all.json" => {
+					let mut platforms =
+						json!({ "mac-os-arm64": { "java-runtime-gamma": build("/decoy.json") } });
+					platforms[platform] =
+						json!({ "java-runtime-gamma": build("/gamma.json"), "jre-legacy": [] });
+					Response::json(200, platforms)
+				}
+				"/gamma.json" => Response::new(200, files(&host)),
+				"/bin/java" => Response::new(200, java),
+				"/lib/libjli.so" => Response::new(200, LIBRARY),
+				_ => Response::not_found(),
+			}
+		})
+	}
+
+	// Hosts without Mojang's runtimes (like ARM linux) have nothing to install
+	#[test]
+	fn installs_runtime_for_platform() {
+		let Some(platform) = RuntimeInstaller::get_platform() else {
+			return;
+		};
+		let server = start_server(JAVA, platform);
+		let data_dir = get_temp_dir("runtime");
+		let installer = RuntimeInstaller::with_manifest_url(
+			data_dir.to_str().unwrap(),
+			&format!("{}/all.json", server.get_url()),
+		);
+
+		let home = installer.install("java-runtime-gamma").unwrap();
+		assert_eq!(
+			home,
+			data_dir
+				.join("runtime/java-runtime-gamma")
+				.join(platform)
+				.join("java-runtime-gamma")
+		);
+		assert_eq!(fs::read(home.join("bin/java")).unwrap(), JAVA);
+		assert_eq!(fs::read(home.join("lib/libjli.so")).unwrap(), LIBRARY);
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+
+			let mode = |path: &str| fs::metadata(home.join(path)).unwrap().permissions().mode();
+			assert_ne!(mode("bin/java") & 0o111, 0);
+			assert_eq!(mode("lib/libjli.so") & 0o111, 0);
+			assert_eq!(
+				fs::read_link(home.join("bin/jre-java")).unwrap(),
+				PathBuf::from("java")
+			);
+		}
+
+		let paths: Vec<String> = server
+			.get_requests()
+			.into_iter()
+			.map(|request| request.path)
+			.collect();
+		assert!(!paths.contains(&String::from("/decoy.json")));
+
+		// Installed runtime of the same build isn't downloaded again
+		installer.install("java-runtime-gamma").unwrap();
+		let downloads = server
+			.get_requests()
+			.into_iter()
+			.filter(|request| request.path == "/gamma.json" || request.path == "/bin/java")
+			.count();
+		assert_eq!(downloads, 2);
+
+		fs::remove_dir_all(data_dir).unwrap();
+	}
+
+	#[test]
+	fn unknown_component_fails() {
+		let Some(platform) = RuntimeInstaller::get_platform() else {
+			return;
+		};
+		let server = start_server(JAVA, platform);
+		let data_dir = get_temp_dir("runtime-unknown");
+		let installer = RuntimeInstaller::with_manifest_url(
+			data_dir.to_str().unwrap(),
+			&format!("{}/all.json", server.get_url()),
+		);
+
+		assert!(installer.install("jre-legacy").is_err());
+		assert!(installer.install("java-runtime-delta").is_err());
+
+		fs::remove_dir_all(data_dir).unwrap();
+	}
+
+	#[test]
+	fn corrupted_file_fails() {
+		let Some(platform) = RuntimeInstaller::get_platform() else {
+			return;
+		};
+		let server = start_server(b"corrupted", platform);
+		let data_dir = get_temp_dir("runtime-corrupted");
+		let installer = RuntimeInstaller::with_manifest_url(
+			data_dir.to_str().unwrap(),
+			&format!("{}/all.json", server.get_url()),
+		);
+
+		assert!(installer.install("java-runtime-gamma").is_err());
+		// Broken install isn't remembered as finished one
+		assert!(!data_dir
+			.join("runtime/java-runtime-gamma")
+			.join(platform)
+			.join(VERSION_FILE)
+			.exists());
+
+		fs::remove_dir_all(data_dir).unwrap();
+	}
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::util::error::Error;
+
+use super::textfile::RetrievePlainText;
+use super::vanilla::{Manifest, VanillaManifest};
+
+/* VERSION SOURCES
+* Official manifest lacks very old builds and community preserved versions
+* Additional sources are listed in optional data/version_sources.json:
+* [
+*   {
+*     "name": "betacraft",
+*     "url": "https://example.org/versions.json",
+*     "versions": "/versions",
+*     "fields": { "id": "id", "type": "type", "url": "url", "sha1": "sha1", "releaseTime": "releaseTime" }
+*   },
+*   ...
+* ]
+* "versions" is a JSON pointer to array of entries, "fields" - names of entry fields
+* (both default to official manifest format). Relative package urls are resolved from source url
+* Official versions win over versions with the same id from other sources
+*/
+
+const SOURCES_PATH: &str = "data/version_sources.json";
+
+pub const OFFICIAL_SOURCE: &str = "mojang";
+
+pub trait VersionSource {
+	fn get_name(&self) -> &str;
+
+	// Manifests of version packages, tagged with name of source
+	fn get_versions(&self) -> Result<Vec<VanillaManifest>, Error>;
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonSource {
+	pub name: String,
+	pub url: String,
+	pub versions: Option<String>,
+	pub fields: SourceFields,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SourceFields {
+	pub id: String,
+	pub r#type: String,
+	pub url: String,
+	pub sha1: String,
+	pub release_time: String,
+}
+
+impl Default for SourceFields {
+	fn default() -> Self {
+		Self {
+			id: String::from("id"),
+			r#type: String::from("type"),
+			url: String::from("url"),
+			sha1: String::from("sha1"),
+			release_time: String::from("releaseTime"),
+		}
+	}
+}
+
+// Versions, that are already merged from other sources, keep their tags
+impl VersionSource for Manifest {
+	fn get_name(&self) -> &str {
+		OFFICIAL_SOURCE
+	}
+
+	fn get_versions(&self) -> Result<Vec<VanillaManifest>, Error> {
+		Ok(self
+			.versions
+			.iter()
+			.cloned()
+			.map(|mut version| {
+				if version.source.is_empty() {
+					version.source = String::from(OFFICIAL_SOURCE);
+				}
+				version
+			})
+			.collect())
+	}
+}
+
+impl JsonSource {
+	// Absence of list is not an error
+	pub fn load_all() -> Result<Vec<Self>, Error> {
+		let path = Path::new(SOURCES_PATH);
+		if !path.exists() {
+			return Ok(Default::default());
+		}
+
+		Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+	}
+
+	fn get_field(entry: &Value, name: &str) -> String {
+		match entry.get(name) {
+			Some(Value::String(value)) => value.clone(),
+			Some(Value::Null) | None => String::new(),
+			Some(value) => value.to_string(),
+		}
+	}
+
+	fn resolve_url(&self, url: String) -> String {
+		if url.is_empty() || url.contains("://") {
+			return url;
+		}
+
+		match self.url.rsplit_once('/') {
+			Some((base, _)) => format!("{base}/{}", url.trim_start_matches('/')),
+			None => url,
+		}
+	}
+
+	fn parse_versions(&self, text: &str) -> Result<Vec<VanillaManifest>, Error> {
+		let root: Value = serde_json::from_str(text)?;
+
+		let entries = root
+			.pointer(self.versions.as_deref().unwrap_or("/versions"))
+			.and_then(Value::as_array)
+			.ok_or_else(|| {
+				Error::Default(format!("version source {} has no versions list", self.name))
+			})?;
+
+		Ok(entries
+			.iter()
+			.map(|entry| VanillaManifest {
+				id: Self::get_field(entry, &self.fields.id),
+				r#type: Self::get_field(entry, &self.fields.r#type),
+				url: self.resolve_url(Self::get_field(entry, &self.fields.url)),
+				release_time: Self::get_field(entry, &self.fields.release_time),
+				hash: Self::get_field(entry, &self.fields.sha1),
+				source: self.name.clone(),
+				..Default::default()
+			})
+			.filter(|manifest| !manifest.id.is_empty() && !manifest.url.is_empty())
+			.collect())
+	}
+}
+
+impl VersionSource for JsonSource {
+	fn get_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_versions(&self) -> Result<Vec<VanillaManifest>, Error> {
+		let text =
+			Self::retrieve_text(&format!("data/sources/{}.json", self.name), &self.url, None)?;
+		self.parse_versions(&text)
+	}
+}
+impl RetrievePlainText for JsonSource {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	struct StaticSource(Vec<VanillaManifest>);
+
+	impl VersionSource for StaticSource {
+		fn get_name(&self) -> &str {
+			"static"
+		}
+
+		fn get_versions(&self) -> Result<Vec<VanillaManifest>, Error> {
+			Ok(self.0.clone())
+		}
+	}
+
+	fn version(id: &str, url: &str) -> VanillaManifest {
+		VanillaManifest {
+			id: id.to_string(),
+			url: url.to_string(),
+			..Default::default()
+		}
+	}
+
+	fn official() -> Manifest {
+		serde_json::from_value(json!({
+			"latest": { "release": "1.20.1" },
+			"versions": [
+				{ "id": "1.20.1", "type": "release", "url": "https://piston-meta.mojang.com/1.20.1.json", "sha1": "aa" },
+				{ "id": "b1.7.3", "type": "old_beta", "url": "https://piston-meta.mojang.com/b1.7.3.json", "sha1": "bb" }
+			]
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn maps_custom_fields() {
+		let source: JsonSource = serde_json::from_value(json!({
+			"name": "archive",
+			"url": "https://example.org/list/versions.json",
+			"versions": "/data/builds",
+			"fields": { "id": "name", "type": "kind", "url": "json", "sha1": "hash", "releaseTime": "released" }
+		}))
+		.unwrap();
+
+		let text = json!({
+			"data": {
+				"builds": [
+					{ "name": "a1.0.4", "kind": "old_alpha", "json": "a1.0.4.json", "hash": "cc", "released": 1283000000 },
+					{ "name": "no-url", "kind": "old_alpha" },
+					{ "kind": "old_alpha", "json": "no-id.json" }
+				]
+			}
+		});
+		let versions = source.parse_versions(&text.to_string()).unwrap();
+
+		assert_eq!(versions.len(), 1);
+		assert_eq!(versions[0].id, "a1.0.4");
+		assert_eq!(versions[0].r#type, "old_alpha");
+		assert_eq!(versions[0].url, "https://example.org/list/a1.0.4.json");
+		assert_eq!(versions[0].hash, "cc");
+		// Numbers are taken as they are written
+		assert_eq!(versions[0].release_time, "1283000000");
+		assert_eq!(versions[0].source, "archive");
+	}
+
+	#[test]
+	fn default_fields_follow_official_format() {
+		let source = JsonSource {
+			name: String::from("mirror"),
+			url: String::from("https://example.org/manifest.json"),
+			..Default::default()
+		};
+
+		let versions = source
+			.parse_versions(&serde_json::to_string(&official()).unwrap())
+			.unwrap();
+		assert_eq!(versions.len(), 2);
+		assert_eq!(versions[0].id, "1.20.1");
+		assert_eq!(versions[0].hash, "aa");
+
+		assert!(source.parse_versions(r#"{ "builds": [] }"#).is_err());
+	}
+
+	#[test]
+	fn resolves_relative_urls() {
+		let source = JsonSource {
+			url: String::from("https://example.org/list/versions.json"),
+			..Default::default()
+		};
+
+		assert_eq!(
+			source.resolve_url(String::from("b1.7.3.json")),
+			"https://example.org/list/b1.7.3.json"
+		);
+		assert_eq!(
+			source.resolve_url(String::from("/packages/b1.7.3.json")),
+			"https://example.org/list/packages/b1.7.3.json"
+		);
+		assert_eq!(
+			source.resolve_url(String::from("https://cdn.example.org/b1.7.3.json")),
+			"https://cdn.example.org/b1.7.3.json"
+		);
+		assert_eq!(source.resolve_url(String::new()), "");
+	}
+
+	#[test]
+	fn official_versions_win() {
+		let official = official();
+		let mut manifest = Manifest::default();
+		manifest.merge(&official).unwrap();
+		manifest
+			.merge(&StaticSource(vec![
+				version("b1.7.3", "https://example.org/b1.7.3.json"),
+				version("c0.0.13a", "https://example.org/c0.0.13a.json"),
+			]))
+			.unwrap();
+
+		let tags: Vec<(&str, &str)> = manifest
+			.versions
+			.iter()
+			.map(|version| (version.id.as_str(), version.source.as_str()))
+			.collect();
+		assert_eq!(
+			tags,
+			[
+				("1.20.1", OFFICIAL_SOURCE),
+				("b1.7.3", OFFICIAL_SOURCE),
+				("c0.0.13a", "static"),
+			]
+		);
+		assert_eq!(
+			manifest.find("b1.7.3").unwrap().url,
+			"https://piston-meta.mojang.com/b1.7.3.json"
+		);
+	}
+
+	#[test]
+	fn merged_manifest_keeps_tags() {
+		let mut manifest = Manifest::default();
+		manifest
+			.merge(&StaticSource(vec![version(
+				"c0.0.13a",
+				"https://example.org/c.json",
+			)]))
+			.unwrap();
+
+		let versions = manifest.get_versions().unwrap();
+		assert_eq!(versions[0].source, "static");
+	}
+}
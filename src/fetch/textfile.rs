@@ -46,6 +46,6 @@ pub trait RetrievePlainText {
 	}
 }
 
-fn check_existance(path: &Path, hash: &String) -> bool {
+fn check_existance(path: &Path, hash: &str) -> bool {
 	Path::exists(path) && *hash.to_uppercase() == hash_file(path, Algorithm::SHA1)
 }
\ No newline at end of file
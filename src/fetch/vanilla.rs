@@ -1,6 +1,5 @@
 use std::fs;
-use std::io;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use std::collections::HashMap;
 
@@ -10,6 +9,10 @@ use checksums::{hash_file, Algorithm};
 
 use crate::util::error::Error;
 
+use super::lwjgl::LwjglReplacements;
+use super::maven::{self, Coordinate};
+use super::natives::Natives;
+use super::sources::{JsonSource, VersionSource};
 use super::textfile::RetrievePlainText;
 
 /* MANIFEST
@@ -22,6 +25,8 @@ use super::textfile::RetrievePlainText;
 
 const URL_MANIFEST: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
 
+const LOCAL_VERSIONS_DIR: &str = "data/versions";
+
 #[derive(Default, Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Manifest {
@@ -42,6 +47,9 @@ pub struct VanillaManifest {
 	pub release_time: String,
 	#[serde(rename = "sha1")]
 	pub hash: String,
+	// Name of version source
+	#[serde(skip)]
+	pub source: String,
 }
 
 /* VERSION PACKAGE
@@ -57,6 +65,9 @@ pub struct VanillaManifest {
 * - "arguments", "minecraftArguments": list/array of arguments, that should be passed to correspond minecraft version and jvm at launch
 * - "releaseTime": game release date
 * - "type": version type (snapshot/release)
+* Modloaders and custom versions also may have:
+* - "inheritsFrom": id of parent version, which package should be merged with this one
+* - "jar": id of version, which client jar is used (root one of inheritance chain by default)
 */
 
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -76,6 +87,8 @@ pub struct Vanilla {
 	pub arguments: Option<ExecArgumentsArray>,
 	pub release_time: String,
 	pub r#type: String,
+	pub inherits_from: Option<String>,
+	pub jar: Option<String>,
 }
 #[derive(Default, Debug, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -83,49 +96,67 @@ pub struct JavaVersion {
 	pub component: String,
 	pub major_version: i32,
 }
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Library {
 	pub downloads: LibraryDownloads,
 	pub name: String,
 	pub rules: Option<Vec<Rule>>,
+	// [OS name] [classifier], classifier may contain ${arch}
+	pub natives: Option<HashMap<String, String>>,
+	pub extract: Option<Extract>,
+	// Modloaders give only maven repository, artifact path is built from name
+	pub url: Option<String>,
+	#[serde(rename = "sha1")]
+	pub hash: Option<String>,
+	pub size: Option<usize>,
 }
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Extract {
+	pub exclude: Vec<String>,
+}
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct LibraryDownloads {
 	pub artifact: Option<DataObject>,
 	pub classifiers: Option<HashMap<String, DataObject>>,
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Rule {
 	action: Action,
 	os: Option<OS>,
 }
-#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum Action {
 	Allow,
 	Disallow,
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub struct OS {
 	name: Option<OSName>,
 	arch: Option<OSArch>,
 }
-#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum OSName {
 	Windows,
 	Linux,
-	OSX,
+	#[serde(rename = "osx")]
+	Osx,
 	Undefined,
 }
-#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum OSArch {
 	X86,
 	X86_64,
+	#[serde(alias = "aarch64")]
+	Arm64,
+	#[serde(alias = "arm32")]
+	Arm,
 	Undefined,
 }
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -181,8 +212,8 @@ impl Rule {
 		if let Some(os) = self.os.as_ref() {
 			if let Some(name) = os.name.as_ref() {
 				let hostname = host.name.as_ref().unwrap_or(&OSName::Undefined);
-				return (self.action == Action::Allow && *hostname == *name)
-					|| (self.action == Action::Disallow && *hostname != *name);
+				return (self.action == Action::Allow && hostname == name)
+					|| (self.action == Action::Disallow && hostname != name);
 			}
 			if let Some(arch) = os.arch.as_ref() {
 				let hostarch = host.arch.as_ref().unwrap_or(&OSArch::Undefined);
@@ -197,7 +228,7 @@ impl Rule {
 	pub fn check_complex(rules: &Vec<Self>, host: &OS) -> bool {
 		let mut result = false;
 		for rule in rules {
-			result = rule.check(&host);
+			result = rule.check(host);
 		}
 
 		result
@@ -223,23 +254,126 @@ impl OS {
 
 impl OSName {
 	pub fn current() -> Self {
-		match &std::env::consts::OS {
-			&"linux" => OSName::Linux,
-			&"windows" => OSName::Windows,
-			&"macos" => OSName::OSX,
+		match std::env::consts::OS {
+			"linux" => OSName::Linux,
+			"windows" => OSName::Windows,
+			"macos" => OSName::Osx,
 			_ => OSName::Undefined,
 		}
 	}
+
+	// Key used by "natives" maps and "natives-*" classifiers
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			OSName::Windows => "windows",
+			OSName::Linux => "linux",
+			OSName::Osx => "osx",
+			OSName::Undefined => "undefined",
+		}
+	}
 }
 
 impl OSArch {
 	pub fn current() -> Self {
-		match &std::env::consts::ARCH {
-			&"x86" => OSArch::X86,
-			&"x86_64" => OSArch::X86_64,
+		match std::env::consts::ARCH {
+			"x86" => OSArch::X86,
+			"x86_64" => OSArch::X86_64,
+			"aarch64" => OSArch::Arm64,
+			"arm" => OSArch::Arm,
 			_ => OSArch::Undefined,
 		}
 	}
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			OSArch::X86 => "x86",
+			OSArch::X86_64 => "x86_64",
+			OSArch::Arm64 => "arm64",
+			OSArch::Arm => "arm32",
+			OSArch::Undefined => "undefined",
+		}
+	}
+
+	// Value for ${arch} inside native classifiers
+	pub fn bits(&self) -> &'static str {
+		match self {
+			OSArch::X86 | OSArch::Arm => "32",
+			_ => "64",
+		}
+	}
+
+	// Architecture of natives jar, judging by it's classifier suffix
+	// ("natives-linux-arm64", "natives-windows-x86"). No suffix means x86_64
+	pub fn from_classifier(classifier: &str) -> Self {
+		match classifier.rsplit('-').next() {
+			Some("arm64" | "aarch64" | "aarch_64") => OSArch::Arm64,
+			Some("arm32" | "arm") => OSArch::Arm,
+			Some("x86" | "x32" | "i386") => OSArch::X86,
+			_ => OSArch::X86_64,
+		}
+	}
+}
+
+impl Library {
+	// Rules and, for natives, architecture check
+	pub fn is_allowed(&self, host: &OS) -> bool {
+		if !Rule::check_some_complex(self.rules.as_ref(), host) {
+			return false;
+		}
+
+		match self.get_classifier() {
+			Some(classifier) if self.is_native_artifact() => {
+				let hostarch = host.arch.as_ref().unwrap_or(&OSArch::Undefined);
+				OSArch::from_classifier(&classifier) == *hostarch
+			}
+			_ => true,
+		}
+	}
+
+	// Jar, that goes into class path
+	pub fn get_artifact(&self) -> Option<DataObject> {
+		if let Some(artifact) = self.downloads.artifact.as_ref() {
+			return Some(artifact.clone());
+		}
+
+		let repository = self.url.as_ref()?;
+
+		Some(Coordinate::parse(&self.name)?.get_object(
+			repository,
+			self.hash.as_deref(),
+			self.size.unwrap_or_default(),
+		))
+	}
+
+	pub fn get_classifier(&self) -> Option<String> {
+		Coordinate::parse(&self.name)?.classifier
+	}
+
+	// Classifier jar with natives for current host (pre 1.19 style)
+	pub fn get_native_classifier(&self) -> Option<&DataObject> {
+		let natives = self.natives.as_ref()?;
+		let classifiers = self.downloads.classifiers.as_ref()?;
+
+		let hostarch = OSArch::current();
+		let classifier = natives
+			.get(OSName::current().as_str())?
+			.replace("${arch}", hostarch.bits());
+
+		// Some repackaged versions carry "natives-linux-arm64" next to "natives-linux"
+		classifiers
+			.get(&format!("{classifier}-{}", hostarch.as_str()))
+			.or_else(|| classifiers.get(&classifier))
+	}
+
+	// Since 1.19 natives are plain artifacts named like "group:artifact:version:natives-linux"
+	// and filtered out only by rules
+	pub fn is_native_artifact(&self) -> bool {
+		self.natives.is_none()
+			&& self.downloads.artifact.is_some()
+			&& self
+				.get_classifier()
+				.is_some_and(|classifier| classifier.starts_with("natives-"))
+	}
 }
 
 impl Manifest {
@@ -248,31 +382,76 @@ impl Manifest {
 	pub fn new() -> Result<Self, Error> {
 		let path = String::from("data/version_manifest_v2.json");
 
-		Ok(serde_json::from_str(
+		let official: Self = serde_json::from_str(
 			Self::retrieve_text(&path, &URL_MANIFEST.to_string(), None)?.as_str(),
-		)?)
-	}
-
-	pub fn get_for_version(&self, version_id: &String) -> VanillaManifest {
-		match version_id.as_str() {
-			"release" | "snapshot" => self.get_for_version(&self.latest.get(version_id).unwrap()),
-			_ => {
-				if let Some(manifest) = self
-					.versions
-					.iter()
-					.find(|&element| element.id == *version_id)
-				{
-					manifest.clone()
-				} else {
-					print!(
-						"FAILED \"{}\": no such version. Falling back to latest release",
-						version_id
-					);
-
-					self.get_for_version(&"release".to_string())
-				}
+		)?;
+
+		// Official source goes first, so it wins over the same ids from other ones
+		let mut manifest = Self {
+			latest: official.latest.clone(),
+			versions: Default::default(),
+		};
+		manifest.merge(&official)?;
+
+		// Broken third-party source shouldn't prevent launch of other versions
+		for source in JsonSource::load_all()? {
+			if let Err(e) = manifest.merge(&source) {
+				println!("FAILED version source \"{}\": {e}", source.get_name());
+			}
+		}
+
+		Ok(manifest)
+	}
+
+	// Versions with already known ids are skipped
+	pub fn merge(&mut self, source: &dyn VersionSource) -> Result<(), Error> {
+		for mut version in source.get_versions()? {
+			if self.find(&version.id).is_some() {
+				continue;
+			}
+
+			if version.source.is_empty() {
+				version.source = source.get_name().to_string();
+			}
+			self.versions.push(version);
+		}
+
+		Ok(())
+	}
+
+	// Strict lookup, unknown version isn't replaced with latest release
+	pub fn find(&self, version_id: &str) -> Option<&VanillaManifest> {
+		self.versions.iter().find(|&element| element.id == version_id)
+	}
+
+	// "release" and "snapshot" are aliases for latest versions
+	pub fn resolve_alias<'a>(&'a self, version_id: &'a str) -> &'a str {
+		match self.latest.get(version_id) {
+			Some(latest) => latest,
+			None => version_id,
+		}
+	}
+
+	// Versions inside data/versions, that official manifest doesn't know about
+	// (modloaders, hand written or third-party profiles)
+	pub fn get_local_versions(&self) -> Result<Vec<String>, Error> {
+		let root = Path::new(LOCAL_VERSIONS_DIR);
+		if !root.exists() {
+			return Ok(Default::default());
+		}
+
+		let mut result: Vec<String> = Default::default();
+		for entry in fs::read_dir(root)? {
+			let entry = entry?;
+			let id = entry.file_name().to_string_lossy().into_owned();
+
+			if self.find(&id).is_none() && entry.path().join(format!("{id}.json")).exists() {
+				result.push(id);
 			}
 		}
+		result.sort();
+
+		Ok(result)
 	}
 }
 impl RetrievePlainText for Manifest {}
@@ -281,9 +460,89 @@ impl Vanilla {
 	pub fn new(manifest: &VanillaManifest) -> Result<Self, Error> {
 		let path = format!("data/versions/{}/{}.json", manifest.id, manifest.id);
 
-		Ok(serde_json::from_str(
+		let mut vanilla: Self = serde_json::from_str(
 			Self::retrieve_text(&path, &manifest.url, Some(&manifest.hash))?.as_str(),
-		)?)
+		)?;
+		LwjglReplacements::load()?.apply(&mut vanilla.libraries);
+
+		Ok(vanilla)
+	}
+
+	// Official versions come from manifest, everything else - from data/versions
+	pub fn load(version_id: &str, manifest: &Manifest) -> Result<Self, Error> {
+		Self::load_unresolved(version_id, manifest)?
+			.resolve_inheritance(|parent_id| Self::load_unresolved(parent_id, manifest))
+	}
+
+	// Version package as it is, parents aren't merged into it
+	fn load_unresolved(version_id: &str, manifest: &Manifest) -> Result<Self, Error> {
+		let version_id = manifest.resolve_alias(version_id);
+
+		if let Some(version_manifest) = manifest.find(version_id) {
+			return Self::new(version_manifest);
+		}
+
+		if let Some(local) = Self::from_local(version_id)? {
+			return Ok(local);
+		}
+
+		Err(Error::Default(format!(
+			"unknown version {version_id}: it's neither in manifest nor in {LOCAL_VERSIONS_DIR}"
+		)))
+	}
+
+	pub fn from_local(version_id: &str) -> Result<Option<Self>, Error> {
+		let path = format!("{LOCAL_VERSIONS_DIR}/{version_id}/{version_id}.json");
+		if !Path::new(&path).exists() {
+			return Ok(None);
+		}
+
+		let mut vanilla: Self = serde_json::from_str(&fs::read_to_string(&path)?)?;
+		if vanilla.id.is_empty() {
+			vanilla.id = version_id.to_string();
+		}
+		LwjglReplacements::load()?.apply(&mut vanilla.libraries);
+
+		Ok(Some(vanilla))
+	}
+
+	pub fn get_jar_id(&self) -> &str {
+		self.jar.as_deref().unwrap_or(&self.id)
+	}
+
+	pub fn get_client_object(&self) -> Result<DataObject, Error> {
+		let client = self
+			.downloads
+			.get("client")
+			.ok_or_else(|| Error::Default(format!("version {} has no client jar", self.id)))?;
+
+		Ok(DataObject {
+			path: format!(
+				"data/libraries/net/minecraft/client/{}/client-{}-official.jar",
+				self.get_jar_id(),
+				self.get_jar_id()
+			),
+			..client.clone()
+		})
+	}
+
+	// ProGuard mappings of obfuscated client, they're placed like the ones of modloader installers
+	pub fn get_client_mappings_object(&self) -> Result<DataObject, Error> {
+		let mappings = self.downloads.get("client_mappings").ok_or_else(|| {
+			Error::Default(format!(
+				"version {} has no official mappings (they're published since 1.14.4)",
+				self.id
+			))
+		})?;
+
+		Ok(DataObject {
+			path: format!(
+				"data/libraries/net/minecraft/client/{}/client-{}-mappings.txt",
+				self.get_jar_id(),
+				self.get_jar_id()
+			),
+			..mappings.clone()
+		})
 	}
 
 	pub fn get_data_objects(&self) -> Result<Vec<DataObject>, Error> {
@@ -313,7 +572,7 @@ impl Vanilla {
 			.expect("failed to reserve memory for assets pool");
 
 		// Because "objects" is a HashMap, but with useless info as hash ¯\_(ツ)_/¯
-		for (_, asset) in &assets_response.objects {
+		for asset in assets_response.objects.values() {
 			let relpath = format!("{}/{}", &asset.hash[0..2], asset.hash);
 			objects.push(DataObject {
 				path: format!("data/assets/objects/{}", relpath),
@@ -331,72 +590,39 @@ impl Vanilla {
 
 		for library in &self.libraries {
 			// Filtering out foreign natives
-			if !Rule::check_some_complex(library.rules.as_ref(), &host) {
+			if !library.is_allowed(&host) {
 				continue;
 			}
 			// Jar library
-			if library.downloads.artifact.is_some() {
-				let artifact = library.downloads.artifact.as_ref().unwrap();
+			if let Some(artifact) = library.get_artifact() {
 				let path = format!("data/libraries/{}", artifact.path);
 
+				let mut object = DataObject { path, ..artifact };
+				maven::fill_checksum(&mut object);
+				objects.push(object);
+			}
+			// Native dll/so library (pre 1.19 classifier style)
+			if let Some(native) = library.get_native_classifier() {
+				let path = format!("data/libraries/{}", native.path);
 				objects.push(DataObject {
 					path,
-					..artifact.clone()
+					..native.clone()
 				});
 			}
-			// Native dll/so library
-			if library.downloads.classifiers.is_some() {
-				let host = format!("natives-{}", std::env::consts::OS);
-				for (name, native) in library.downloads.classifiers.as_ref().unwrap() {
-					if *name != host {
-						continue;
-					}
-
-					let path = format!("data/libraries/{}", native.path);
-					objects.push(DataObject {
-						path,
-						..native.clone()
-					});
-				}
-			}
 		}
 
 		/*
 			CLIENT
 		*/
 
-		{
-			let path = format!(
-				"data/libraries/net/minecraft/client/{}/client-{}-official.jar",
-				self.id, self.id
-			);
-
-			objects.push(DataObject {
-				path,
-				..self
-					.downloads
-					.get("client")
-					.expect("failed to get minecraft client object")
-					.clone()
-			});
-		}
+		objects.push(self.get_client_object()?);
 
 		/*
 			LOGGING
 		*/
 
-		if let Some(logging) = self.logging.get("client") {
-			let path = format!(
-				"data/assets/objects/{}/{}/{}",
-				&logging.file.hash[0..2],
-				logging.file.hash,
-				logging.file.path
-			);
-
-			objects.push(DataObject {
-				path,
-				..logging.file.clone()
-			});
+		if let Some(logging) = self.get_logging_file("data/assets") {
+			objects.push(logging);
 		}
 
 		if objects.len() > poolsize {
@@ -412,62 +638,32 @@ impl Vanilla {
 	}
 
 	pub fn extract_natives(&self) -> Result<(), Error> {
-		let host = OS::current();
-		let host_str = format!("natives-{}", std::env::consts::OS);
-
-		let root = String::from("data/libraries");
-		let target = PathBuf::from(format!("data/versions/{}/natives", self.id));
-		fs::create_dir_all(&target)?;
-
-		for native in self
-			.libraries
-			.iter()
-			.filter(|&lib| lib.downloads.classifiers.is_some() || lib.rules.is_some())
-		{
-			if !Rule::check_some_complex(native.rules.as_ref(), &host) {
-				continue;
-			}
-			if let Some(classifiers) = native.downloads.classifiers.as_ref() {
-				for (os, object) in classifiers {
-					if *os == host_str {
-						object.extract_to(&root, &target)?;
-						break;
-					}
-				}
-			}
-		}
-
-		Ok(())
+		Natives::new(
+			"data/libraries",
+			format!("data/versions/{}/natives", self.id),
+		)
+		.extract(&self.libraries)
 	}
 
 	pub fn get_class_path(&self, libraries_dir: &String, versions_dir: &String) -> String {
-		let class_separator = match std::env::consts::OS {
-			"windows" => ";",
-			_ => ":",
-		};
+		let host = OS::current();
 
 		self.libraries
 			.iter()
-			.filter(|lib| lib.downloads.artifact.is_some())
-			.map(|lib| {
-				format!(
-					"{}/{}",
-					libraries_dir,
-					lib.downloads.artifact.as_ref().unwrap().path
-				)
-			})
+			.filter(|lib| lib.is_allowed(&host))
+			.filter_map(|lib| lib.get_artifact())
+			.map(|artifact| format!("{}/{}", libraries_dir, artifact.path))
 			.chain(vec![
 				format!(
 					"{}/net/minecraft/client/{}/client-{}-official.jar",
-					libraries_dir, self.id, self.id
-				),
-				format!(
-					"{}/{}/natives",
-					versions_dir, self.id
+					libraries_dir,
+					self.get_jar_id(),
+					self.get_jar_id()
 				),
+				format!("{}/{}/natives", versions_dir, self.id),
 			])
 			.collect::<Vec<_>>()
-			.join(class_separator)
+			.join(get_class_path_separator())
 	}
 
 	pub fn get_launch_arguments(&self, r#type: LaunchArgumentsType) -> Option<Vec<&str>> {
@@ -511,19 +707,22 @@ impl Vanilla {
 }
 impl RetrievePlainText for Vanilla {}
 
+pub fn get_class_path_separator() -> &'static str {
+	match std::env::consts::OS {
+		"windows" => ";",
+		_ => ":",
+	}
+}
+
 impl DataObject {
 	pub fn is_cached(&self) -> bool {
 		let path = Path::new(&self.path);
 
-		Path::exists(path) && self.hash.to_uppercase() == hash_file(path, Algorithm::SHA1)
-	}
-
-	pub fn extract_to(&self, root: &String, target: &PathBuf) -> Result<(), Error> {
-		let bytes = fs::read(format!("{}/{}", root, self.path))?;
-
-		zip_extract::extract(io::Cursor::new(bytes), &target, true)
-			.expect("native jar extraction error");
+		// Some modloader libraries have no hash (or are generated at installation)
+		if self.hash.is_empty() {
+			return Path::exists(path);
+		}
 
-		Ok(())
+		Path::exists(path) && self.hash.to_uppercase() == hash_file(path, Algorithm::SHA1)
 	}
 }
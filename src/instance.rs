@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::Path;
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::error::Error;
+
+/* INSTANCE
+* instance.json inside of instance directory remembers, how instance is launched:
+* {
+*   "version": "1.20.1",
+*   "loader": "fabric:0.15.7",
+*   "java": "/usr/lib/jvm/java-17-openjdk",
+*   "javaVersion": 17,
+*   "memory": "6G",
+*   "preset": "g1",
+*   "jvmArguments": [ "-Dfml.ignorePatchDiscrepancies=true" ],
+*   "gameArguments": [ "--width", "1280" ],
+*   "env": { "MESA_GL_VERSION_OVERRIDE": "4.5" },
+*   "account": "Notch",
+*   "username": "Steve",
+*   "keepLogs": 10
+* }
+* Values from command line (-l, --loader, -j, -m, --preset, -u) override saved ones
+* and are written back after successful update,
+* so "-i [path]" alone launches instance the same way next time
+* "release" and "snapshot" are saved as concrete version, instance isn't moved to newer one
+* Account is only read, "--account" is meant for single launch
+*/
+
+const INSTANCE_FILE: &str = "instance.json";
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Instance {
+	pub version: Option<String>,
+	// Same as --loader: "name[:version]"
+	pub loader: Option<String>,
+	// Java home or executable
+	pub java: Option<String>,
+	// Java, that is required instead of the one from version package
+	pub java_version: Option<u32>,
+	// Heap size or "auto"
+	pub memory: Option<String>,
+	// Named set of JVM flags
+	pub preset: Option<String>,
+	pub jvm_arguments: Vec<String>,
+	pub game_arguments: Vec<String>,
+	pub env: HashMap<String, String>,
+	// Saved account, default one is used otherwise
+	pub account: Option<String>,
+	// Offline player name
+	pub username: Option<String>,
+	// Launch logs, that are kept in logs/launcher
+	pub keep_logs: Option<usize>,
+}
+
+impl Instance {
+	// New instance has nothing saved yet
+	pub fn load(instance_dir: &str) -> Result<Self, Error> {
+		let path = Path::new(instance_dir).join(INSTANCE_FILE);
+		if !path.exists() {
+			return Ok(Self::default());
+		}
+
+		serde_json::from_str(&fs::read_to_string(&path)?)
+			.map_err(|e| Error::Default(format!("wrong instance file {}: {e}", path.display())))
+	}
+
+	pub fn save(&self, instance_dir: &str) -> Result<(), Error> {
+		fs::create_dir_all(instance_dir)?;
+		fs::write(
+			Path::new(instance_dir).join(INSTANCE_FILE),
+			serde_json::to_string_pretty(self)?,
+		)?;
+
+		Ok(())
+	}
+
+	pub fn exists(instance_dir: &str) -> bool {
+		Path::new(instance_dir).join(INSTANCE_FILE).exists()
+	}
+}
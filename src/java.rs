@@ -0,0 +1,321 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::fetch::runtime::RuntimeInstaller;
+use crate::util::error::Error;
+
+/* JAVA
+* Every minecraft version wants it's own java major version ("javaVersion.majorVersion"):
+* 1.16 crashes on new ones, 1.20.5+ refuses to start on java 8
+* Runtimes are searched in:
+* - paths given by user (java home or java executable)
+* - JAVA_HOME
+* - PATH
+* - system wide directories (/usr/lib/jvm/[name], etc.)
+* Major version is taken from "release" file of java home, or from java itself
+* Unless a java path is given, runtime from Mojang ("javaVersion.component") is preferred
+*/
+
+// Versions without "javaVersion" are old enough to want java 8
+const DEFAULT_MAJOR_VERSION: u32 = 8;
+
+// Since 1.18 game doesn't break on newer java
+const FORWARD_COMPATIBLE_SINCE: u32 = 17;
+
+#[cfg(target_os = "windows")]
+const JAVA_EXECUTABLE: &str = "java.exe";
+#[cfg(not(target_os = "windows"))]
+const JAVA_EXECUTABLE: &str = "java";
+
+#[cfg(target_os = "linux")]
+const SYSTEM_JVM_DIRS: [&str; 2] = ["/usr/lib/jvm", "/usr/lib64/jvm"];
+#[cfg(target_os = "macos")]
+const SYSTEM_JVM_DIRS: [&str; 1] = ["/Library/Java/JavaVirtualMachines"];
+#[cfg(target_os = "windows")]
+const SYSTEM_JVM_DIRS: [&str; 2] = [
+	"C:\\Program Files\\Java",
+	"C:\\Program Files\\Eclipse Adoptium",
+];
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+const SYSTEM_JVM_DIRS: [&str; 0] = [];
+
+#[derive(Clone, Debug)]
+pub struct JavaRuntime {
+	pub executable: PathBuf,
+	pub major_version: u32,
+}
+
+#[derive(Default)]
+pub struct JavaDiscovery {
+	paths: Vec<PathBuf>,
+}
+
+impl JavaRuntime {
+	pub fn from_executable(executable: PathBuf) -> Option<Self> {
+		if !executable.is_file() {
+			return None;
+		}
+
+		// [home]/bin/java, but /usr/bin/java is usually a symlink to it
+		let release_version = fs::canonicalize(&executable)
+			.ok()?
+			.parent()
+			.and_then(Path::parent)
+			.and_then(Self::read_release_file);
+
+		let major_version = match release_version {
+			Some(major_version) => major_version,
+			None => Self::ask_executable(&executable)?,
+		};
+
+		Some(Self {
+			executable,
+			major_version,
+		})
+	}
+
+	pub fn from_home(home: &Path) -> Option<Self> {
+		Self::from_executable(home.join("bin").join(JAVA_EXECUTABLE))
+	}
+
+	// JAVA_VERSION="17.0.2" or JAVA_VERSION="1.8.0_292"
+	fn read_release_file(home: &Path) -> Option<u32> {
+		let release = fs::read_to_string(home.join("release")).ok()?;
+
+		release
+			.lines()
+			.find_map(|line| line.strip_prefix("JAVA_VERSION="))
+			.and_then(|version| Self::parse_major_version(version.trim_matches('"')))
+	}
+
+	// Slow way, for runtimes without "release" file
+	fn ask_executable(executable: &Path) -> Option<u32> {
+		let output = Command::new(executable)
+			.args(["-XshowSettings:properties", "-version"])
+			.output()
+			.ok()?;
+
+		String::from_utf8_lossy(&output.stderr)
+			.lines()
+			.find_map(|line| line.trim().strip_prefix("java.version = "))
+			.and_then(Self::parse_major_version)
+	}
+
+	// "1.8.0_292" -> 8, "17.0.2" -> 17, "21" -> 21
+	pub fn parse_major_version(version: &str) -> Option<u32> {
+		let mut numbers = version.split(['.', '_', '-', '+']);
+
+		match numbers.next()?.parse::<u32>().ok()? {
+			1 => numbers.next()?.parse::<u32>().ok(),
+			major => Some(major),
+		}
+	}
+
+	pub fn is_suitable_for(&self, required: u32) -> bool {
+		self.major_version == required
+			|| (required >= FORWARD_COMPATIBLE_SINCE && self.major_version > required)
+	}
+}
+
+impl JavaDiscovery {
+	pub fn new(paths: Vec<PathBuf>) -> Self {
+		Self { paths }
+	}
+
+	pub fn find_all(&self) -> Vec<JavaRuntime> {
+		let mut runtimes: Vec<JavaRuntime> = Default::default();
+
+		for candidate in self.get_candidates() {
+			let Some(runtime) = JavaRuntime::from_executable(candidate) else {
+				continue;
+			};
+
+			// Same runtime may be found through JAVA_HOME, PATH and /usr/lib/jvm
+			let canonical = fs::canonicalize(&runtime.executable).ok();
+			if runtimes
+				.iter()
+				.any(|found| fs::canonicalize(&found.executable).ok() == canonical)
+			{
+				continue;
+			}
+
+			runtimes.push(runtime);
+		}
+
+		runtimes
+	}
+
+	pub fn select_or_install(
+		&self,
+		required: u32,
+		component: &str,
+		installer: &RuntimeInstaller,
+	) -> Result<JavaRuntime, Error> {
+		if self.paths.is_empty() && !component.is_empty() {
+			match installer.install(component) {
+				Ok(home) => match JavaRuntime::from_home(&home) {
+					Some(runtime) => return Ok(runtime),
+					None => println!("WARNING: managed java runtime {component} is broken"),
+				},
+				// Offline or unsupported platform, system java still may be fine
+				Err(e) => println!("WARNING: failed to install java runtime {component}: {e}"),
+			}
+		}
+
+		self.select(required)
+	}
+
+	// Java given with -j wins, when it fits. Otherwise exact major version is preferred,
+	// newer one is used only for versions, which are known to work on them
+	pub fn select(&self, required: u32) -> Result<JavaRuntime, Error> {
+		let required = if required == 0 {
+			DEFAULT_MAJOR_VERSION
+		} else {
+			required
+		};
+
+		if let Some(runtime) = self
+			.get_given_runtimes()
+			.into_iter()
+			.find(|runtime| runtime.is_suitable_for(required))
+		{
+			return Ok(runtime);
+		}
+
+		let runtimes = self.find_all();
+
+		if let Some(runtime) = runtimes
+			.iter()
+			.find(|runtime| runtime.major_version == required)
+		{
+			return Ok(runtime.clone());
+		}
+
+		if let Some(runtime) = runtimes
+			.iter()
+			.filter(|runtime| runtime.is_suitable_for(required))
+			.min_by_key(|runtime| runtime.major_version)
+		{
+			return Ok(runtime.clone());
+		}
+
+		let found = runtimes
+			.iter()
+			.map(|runtime| {
+				format!(
+					"{} ({})",
+					runtime.executable.display(),
+					runtime.major_version
+				)
+			})
+			.collect::<Vec<_>>();
+
+		Err(Error::Default(format!(
+			"java {required} is required, but not found. Found runtimes: [{}]. Install it or pass it's path with --java",
+			found.join(", ")
+		)))
+	}
+
+	fn get_given_runtimes(&self) -> Vec<JavaRuntime> {
+		self.get_given_candidates()
+			.into_iter()
+			.filter_map(JavaRuntime::from_executable)
+			.collect()
+	}
+
+	// Java home or executable
+	fn get_given_candidates(&self) -> Vec<PathBuf> {
+		self.paths
+			.iter()
+			.map(|path| match path.is_dir() {
+				true => path.join("bin").join(JAVA_EXECUTABLE),
+				false => path.clone(),
+			})
+			.collect()
+	}
+
+	fn get_candidates(&self) -> Vec<PathBuf> {
+		let mut candidates = self.get_given_candidates();
+
+		if let Some(home) = std::env::var_os("JAVA_HOME") {
+			candidates.push(PathBuf::from(home).join("bin").join(JAVA_EXECUTABLE));
+		}
+
+		if let Some(path) = std::env::var_os("PATH") {
+			for dir in std::env::split_paths(&path) {
+				candidates.push(dir.join(JAVA_EXECUTABLE));
+			}
+		}
+
+		for root in SYSTEM_JVM_DIRS {
+			let Ok(entries) = fs::read_dir(root) else {
+				continue;
+			};
+			for entry in entries.flatten() {
+				let home = entry.path();
+				// Macos bundles keep java home inside of Contents/Home
+				let home = match home.join("Contents/Home").is_dir() {
+					true => home.join("Contents/Home"),
+					false => home,
+				};
+				candidates.push(home.join("bin").join(JAVA_EXECUTABLE));
+			}
+		}
+
+		candidates
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Java home with "release" file, executable isn't run
+	fn fake_home(name: &str, version: &str) -> PathBuf {
+		let home =
+			std::env::temp_dir().join(format!("rostermine-java-{}-{name}", std::process::id()));
+		fs::create_dir_all(home.join("bin")).unwrap();
+		fs::write(home.join("bin").join(JAVA_EXECUTABLE), "").unwrap();
+		fs::write(
+			home.join("release"),
+			format!("JAVA_VERSION=\"{version}\"\n"),
+		)
+		.unwrap();
+		home
+	}
+
+	#[test]
+	fn parses_major_version() {
+		assert_eq!(JavaRuntime::parse_major_version("1.8.0_292"), Some(8));
+		assert_eq!(JavaRuntime::parse_major_version("17.0.2"), Some(17));
+		assert_eq!(JavaRuntime::parse_major_version("21"), Some(21));
+		assert_eq!(JavaRuntime::parse_major_version("22-ea"), Some(22));
+	}
+
+	#[test]
+	fn given_java_wins_when_suitable() {
+		let home = fake_home("given", "21.0.1");
+		let runtime = JavaDiscovery::new(vec![home.clone()]).select(17).unwrap();
+
+		assert_eq!(runtime.executable, home.join("bin").join(JAVA_EXECUTABLE));
+		assert_eq!(runtime.major_version, 21);
+		fs::remove_dir_all(home).unwrap();
+	}
+
+	#[test]
+	fn given_java_is_skipped_when_unsuitable() {
+		let old = fake_home("old", "1.8.0_292");
+		let new = fake_home("new", "17.0.2");
+
+		// Unsuitable one goes first, suitable given one wins over system javas
+		let discovery = JavaDiscovery::new(vec![old.clone(), new.clone()]);
+		let runtime = discovery.select(17).unwrap();
+
+		assert_eq!(runtime.executable, new.join("bin").join(JAVA_EXECUTABLE));
+		assert_eq!(runtime.major_version, 17);
+		assert_ne!(runtime.executable, old.join("bin").join(JAVA_EXECUTABLE));
+		fs::remove_dir_all(old).unwrap();
+		fs::remove_dir_all(new).unwrap();
+	}
+}
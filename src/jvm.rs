@@ -0,0 +1,227 @@
+use std::fs;
+use std::path::Path;
+
+use crate::java::JavaRuntime;
+use crate::util::error::Error;
+
+/* JVM OPTIONS
+* Heap size is given like "512M", "6G" or "auto". Auto size is counted from mods inside
+* of instance and is limited by half of physical memory:
+* - 2G + 32M for every mod, rounded up to 256M, at least 1G
+* Presets are named sets of JVM flags:
+* - default: nothing, JVM decides by itself
+* - g1: tuned G1 collector (known as Aikar's flags), heap is fully committed at start
+* - zgc: low pause collector, generational since java 21 (flag is needed only on 21-23)
+*/
+
+pub const DEFAULT_GAME_MEMORY: &str = "4G";
+
+const AUTO_BASE_MB: u64 = 2048;
+const AUTO_PER_MOD_MB: u64 = 32;
+const AUTO_STEP_MB: u64 = 256;
+const MIN_HEAP_MB: u64 = 1024;
+// Initial heap of presets, that don't commit everything at start
+const INITIAL_HEAP_MB: u64 = 1024;
+
+const G1_FLAGS: [&str; 18] = [
+	"-XX:+UseG1GC",
+	"-XX:+ParallelRefProcEnabled",
+	"-XX:MaxGCPauseMillis=200",
+	"-XX:+UnlockExperimentalVMOptions",
+	"-XX:+DisableExplicitGC",
+	"-XX:+AlwaysPreTouch",
+	"-XX:G1NewSizePercent=30",
+	"-XX:G1MaxNewSizePercent=40",
+	"-XX:G1HeapRegionSize=8M",
+	"-XX:G1ReservePercent=20",
+	"-XX:G1HeapWastePercent=5",
+	"-XX:G1MixedGCCountTarget=4",
+	"-XX:InitiatingHeapOccupancyPercent=15",
+	"-XX:G1MixedGCLiveThresholdPercent=90",
+	"-XX:G1RSetUpdatingPauseTimePercent=5",
+	"-XX:SurvivorRatio=32",
+	"-XX:+PerfDisableSharedMem",
+	"-XX:MaxTenuringThreshold=1",
+];
+
+// ZGC is production ready since java 15, generational mode appeared in 21 behind a flag.
+// Since 24 it's the only mode and -XX:+ZGenerational is obsolete
+const ZGC_SINCE: u32 = 15;
+const GENERATIONAL_ZGC_SINCE: u32 = 21;
+const GENERATIONAL_ZGC_FLAG_UNTIL: u32 = 23;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum HeapSize {
+	Megabytes(u64),
+	Auto,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum JvmPreset {
+	Default,
+	G1,
+	Zgc,
+}
+
+impl HeapSize {
+	pub fn parse(size: &str) -> Result<Self, Error> {
+		let wrong_size = || {
+			Error::Default(format!(
+				"wrong memory size: {size}. Use [number]M, [number]G or auto"
+			))
+		};
+
+		if size.eq_ignore_ascii_case("auto") {
+			return Ok(Self::Auto);
+		}
+		if !size.is_ascii() {
+			return Err(wrong_size());
+		}
+
+		let (number, unit) = size.split_at(size.len().saturating_sub(1));
+		let number: u64 = number.parse().map_err(|_| wrong_size())?;
+
+		let megabytes = match unit {
+			"K" | "k" => number / 1024,
+			"M" | "m" => number,
+			"G" | "g" => number * 1024,
+			_ => return Err(wrong_size()),
+		};
+
+		match megabytes {
+			0 => Err(wrong_size()),
+			_ => Ok(Self::Megabytes(megabytes)),
+		}
+	}
+
+	pub fn get_megabytes(&self, instance_dir: &str) -> u64 {
+		match self {
+			Self::Megabytes(megabytes) => *megabytes,
+			Self::Auto => {
+				let wanted = AUTO_BASE_MB + AUTO_PER_MOD_MB * count_mods(instance_dir);
+				let wanted = wanted.div_ceil(AUTO_STEP_MB) * AUTO_STEP_MB;
+
+				// Without knowing memory size it's the same as before
+				let limit = get_physical_memory()
+					.map(|total| total / 2)
+					.unwrap_or(AUTO_BASE_MB * 2);
+
+				wanted.min(limit).max(MIN_HEAP_MB)
+			}
+		}
+	}
+}
+
+impl JvmPreset {
+	pub fn from_name(name: &str) -> Result<Self, Error> {
+		match name.to_lowercase().as_str() {
+			"default" => Ok(Self::Default),
+			"g1" => Ok(Self::G1),
+			"zgc" => Ok(Self::Zgc),
+			_ => Err(Error::Default(format!(
+				"unknown jvm preset: {name}. Available: default, g1, zgc"
+			))),
+		}
+	}
+
+	// Heap arguments go first
+	pub fn get_arguments(&self, heap_mb: u64, java: &JavaRuntime) -> Vec<String> {
+		let initial_mb = match self {
+			Self::G1 => heap_mb,
+			_ => heap_mb.min(INITIAL_HEAP_MB),
+		};
+
+		let mut arguments = vec![format!("-Xms{initial_mb}M"), format!("-Xmx{heap_mb}M")];
+
+		match self {
+			Self::Default => {}
+			Self::G1 => arguments.extend(G1_FLAGS.iter().map(|flag| flag.to_string())),
+			Self::Zgc if java.major_version < ZGC_SINCE => {
+				println!(
+					"WARNING: ZGC needs java {ZGC_SINCE}+, java {} uses it's default collector",
+					java.major_version
+				);
+			}
+			Self::Zgc => {
+				arguments.push(String::from("-XX:+UseZGC"));
+				if (GENERATIONAL_ZGC_SINCE..=GENERATIONAL_ZGC_FLAG_UNTIL)
+					.contains(&java.major_version)
+				{
+					arguments.push(String::from("-XX:+ZGenerational"));
+				}
+			}
+		}
+
+		arguments
+	}
+}
+
+// Jars inside of [instance]/mods, disabled ones have other extension
+pub fn count_mods(instance_dir: &str) -> u64 {
+	let Ok(entries) = fs::read_dir(Path::new(instance_dir).join("mods")) else {
+		return 0;
+	};
+
+	entries
+		.filter_map(Result::ok)
+		.filter(|entry| entry.path().extension().is_some_and(|ext| ext == "jar"))
+		.count() as u64
+}
+
+// In megabytes
+#[cfg(target_os = "linux")]
+pub fn get_physical_memory() -> Option<u64> {
+	// "MemTotal:       16318464 kB"
+	fs::read_to_string("/proc/meminfo")
+		.ok()?
+		.lines()
+		.find_map(|line| line.strip_prefix("MemTotal:"))?
+		.split_whitespace()
+		.next()?
+		.parse::<u64>()
+		.ok()
+		.map(|kilobytes| kilobytes / 1024)
+}
+#[cfg(target_os = "macos")]
+pub fn get_physical_memory() -> Option<u64> {
+	let output = std::process::Command::new("sysctl")
+		.args(["-n", "hw.memsize"])
+		.output()
+		.ok()?;
+
+	String::from_utf8_lossy(&output.stdout)
+		.trim()
+		.parse::<u64>()
+		.ok()
+		.map(|bytes| bytes / 1048576)
+}
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn get_physical_memory() -> Option<u64> {
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	fn get_zgc_arguments(major_version: u32) -> Vec<String> {
+		let java = JavaRuntime {
+			executable: PathBuf::from("java"),
+			major_version,
+		};
+		JvmPreset::Zgc.get_arguments(4096, &java)
+	}
+
+	#[test]
+	fn generational_flag_only_for_java_21_to_23() {
+		let generational = String::from("-XX:+ZGenerational");
+
+		assert!(!get_zgc_arguments(17).contains(&generational));
+		assert!(get_zgc_arguments(21).contains(&generational));
+		assert!(get_zgc_arguments(23).contains(&generational));
+		assert!(!get_zgc_arguments(24).contains(&generational));
+		assert!(get_zgc_arguments(24).contains(&String::from("-XX:+UseZGC")));
+	}
+}
@@ -1,37 +1,500 @@
 mod arguments;
+mod auth;
+mod crash;
+mod deobf;
 mod fetch;
+mod instance;
+mod java;
+mod jvm;
+mod registry;
+mod server;
+mod supervisor;
 mod util;
 
-use fetch::{vanilla::Manifest, minecraft::Minecraft};
+use std::io::{self, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fetch::{vanilla::Manifest, vanilla::Vanilla, minecraft::Minecraft};
 use util::error::Error;
 use arguments::Argument;
+use auth::microsoft::{Endpoints, MicrosoftAuth};
+use auth::accounts::AccountStore;
+use auth::offline::{self, DEFAULT_USERNAME};
+use auth::yggdrasil::YggdrasilAuth;
+use auth::AuthSession;
+use crash::CrashReport;
+use deobf::Mappings;
+use fetch::modloader;
+use fetch::runtime::RuntimeInstaller;
+use instance::Instance;
+use java::{JavaDiscovery, JavaRuntime};
+use jvm::{HeapSize, JvmPreset};
+use registry::{InstanceLock, Registry, RegistryEntry};
+use server::{Server, DEFAULT_SERVER_MEMORY};
+use supervisor::{Supervisor, DEFAULT_KEPT_LOGS};
 
 fn main() -> Result<(), Error> {
 	let working_dir = std::env::current_dir()?;
 	let working_dir = working_dir.display();
 
-	let mut version_id = String::from("release");
+	let mut version_id: Option<String> = None;
+	let mut loader: Option<String> = None;
 
 	let mut data_dir = format!("{working_dir}/data");
 	let mut instance_dir = format!("{working_dir}/instances/Default");
+	let mut server_dir = format!("{working_dir}/servers/Default");
+
+	let mut java_paths: Vec<PathBuf> = Default::default();
+	let mut memory: Option<String> = None;
+	let mut preset: Option<String> = None;
+
+	let mut list_versions = false;
+	let mut online = false;
+	let mut detach = false;
+	let mut account: Option<String> = None;
+	let mut username: Option<String> = None;
+	let mut auth_server: Option<String> = None;
+	let mut command: Vec<String> = Default::default();
 
 	for arg in Argument::get_parsed()? {
 		match arg {
-			Argument::SetVersion(id) => version_id = id,
+			Argument::SetVersion(id) => version_id = Some(id),
+			Argument::SetLoader(name) => loader = Some(name),
 			Argument::SetInstanceDir(dir) => instance_dir = dir,
 			Argument::SetDataDir(dir) => data_dir = dir,
+			Argument::AddJavaPath(path) => java_paths.push(PathBuf::from(path)),
+			Argument::SetServerDir(dir) => server_dir = dir,
+			Argument::SetMemory(size) => memory = Some(size),
+			Argument::SetPreset(name) => preset = Some(name),
+			Argument::SetAccount(name) => account = Some(name),
+			Argument::SetUsername(name) => username = Some(name),
+			Argument::SetAuthServer(url) => auth_server = Some(url),
+			Argument::Positional(value) => command.push(value),
+			Argument::ListVersions => list_versions = true,
+			Argument::UseOnlineAuth => online = true,
+			Argument::Detach => detach = true,
 			Argument::GetHelp => Argument::print_help_and_exit()?,
 			Argument::GetProgramVersion => Argument::print_version_and_exit()?,
 		}
 	}
 
+	// Accounts and running games don't need version manifest
+	match command.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+		["account", ref arguments @ ..] => {
+			return manage_accounts(&data_dir, arguments, auth_server.as_deref())
+		}
+		["ps"] => {
+			for game in Registry::load(&data_dir)?.get_games() {
+				let uptime = game.get_uptime();
+				println!(
+					"{}\t{}\t{}h {:02}m\t{}",
+					game.pid,
+					game.version,
+					uptime / 3600,
+					uptime % 3600 / 60,
+					game.instance
+				);
+			}
+			return Ok(());
+		}
+		["stop", instance] => {
+			let mut registry = Registry::load(&data_dir)?;
+			let games = registry.find(instance);
+			if games.is_empty() {
+				return Err(Error::Default(format!(
+					"instance {instance} is not running. See \"ps\""
+				)));
+			}
+
+			for game in games {
+				println!("Stopping game {} ({}). . .", game.pid, game.instance);
+				game.stop()?;
+				registry.remove(game.pid)?;
+			}
+			return Ok(());
+		}
+		_ => {}
+	}
+
 	let manifest = Manifest::new()?;
-	let version = manifest.get_for_version(&version_id);
 
-	println!("\nUpdating version {}. . .", &version.id);
-	let version = Minecraft::new(data_dir, instance_dir, &version.id)?;
-	version.update()?;
-	version.launch()?;
+	// Command line values override saved ones
+	let mut instance = Instance::load(&instance_dir)?;
+	let mut instance_changed = version_id.is_some()
+		|| loader.is_some()
+		|| !java_paths.is_empty()
+		|| memory.is_some()
+		|| preset.is_some()
+		|| username.is_some()
+		|| !Instance::exists(&instance_dir);
+
+	if let Some(id) = version_id.take() {
+		instance.version = Some(id);
+	}
+	if let Some(name) = loader.take() {
+		instance.loader = Some(name);
+	}
+	// Alias is resolved, so instance stays on the version it was created with
+	let version_id = manifest
+		.resolve_alias(instance.version.as_deref().unwrap_or("release"))
+		.to_string();
+	if instance.version.as_ref() != Some(&version_id) {
+		instance.version = Some(version_id.clone());
+		instance_changed = true;
+	}
+
+	if list_versions {
+		// Loader versions for game version
+		if instance.loader.is_some() {
+			let loader = modloader::get(instance.loader.as_deref())?;
+			for id in loader.list_versions(manifest.resolve_alias(&version_id))? {
+				println!("{id}\t{}", loader.get_name());
+			}
+			return Ok(());
+		}
+
+		for version in &manifest.versions {
+			println!("{}\t{}\t{}", version.id, version.r#type, version.source);
+		}
+		for id in manifest.get_local_versions()? {
+			println!("{id}\tlocal");
+		}
+		return Ok(());
+	}
+
+	let runtime_installer = RuntimeInstaller::new(&data_dir);
+
+	match command.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+		[] => {}
+		["server", "install", id] => {
+			let server = Server::new(&data_dir, &server_dir, id, &manifest)?;
+			println!(
+				"\nInstalling server {} into {server_dir}. . .",
+				server.get_id()
+			);
+			return server.install();
+		}
+		["deobf"] => return deobfuscate(&version_id, &instance, &manifest, &instance_dir, None),
+		["deobf", path] => {
+			return deobfuscate(&version_id, &instance, &manifest, &instance_dir, Some(path))
+		}
+		["server", "run"] => {
+			let server = Server::open(&data_dir, &server_dir, &manifest)?;
+			let java = select_java(
+				&JavaDiscovery::new(java_paths),
+				&runtime_installer,
+				server.get_java_major_version(),
+				server.get_java_component(),
+			)?;
+			let heap = HeapSize::parse(memory.as_deref().unwrap_or(DEFAULT_SERVER_MEMORY))?
+				.get_megabytes(&server_dir);
+			return server.run(&java, &format!("{heap}M"));
+		}
+		_ => {
+			return Err(Error::Default(format!(
+				"unknown command: {}",
+				command.join(" ")
+			)))
+		}
+	}
+
+	if let Some(path) = java_paths.first() {
+		instance.java = Some(path.to_string_lossy().into_owned());
+	}
+	if let Some(size) = memory {
+		HeapSize::parse(&size)?;
+		instance.memory = Some(size);
+	}
+	if let Some(name) = preset {
+		JvmPreset::from_name(&name)?;
+		instance.preset = Some(name);
+	}
+	if let Some(name) = username.as_ref() {
+		offline::validate_name(name)?;
+		instance.username = Some(name.clone());
+	}
+
+	// Instance java is used, when none was given
+	if java_paths.is_empty() {
+		java_paths.extend(instance.java.iter().map(PathBuf::from));
+	}
+	let java_discovery = JavaDiscovery::new(java_paths);
+
+	// Choice from command line goes first, then instance one, then default account
+	let mut accounts = AccountStore::load(&data_dir)?;
+	let (account, username) = match (account, username) {
+		(None, None) => (instance.account.clone(), instance.username.clone()),
+		chosen => chosen,
+	};
+	let account = match username {
+		Some(_) => account,
+		None => account.or_else(|| accounts.get_default().map(|account| account.uuid.clone())),
+	};
+
+	let session = match (online, account) {
+		(true, _) => {
+			let session = match auth_server.as_deref() {
+				Some(url) => login_yggdrasil(url)?.0,
+				None => {
+					let auth = MicrosoftAuth::new(Endpoints::load()?);
+					auth.get_session(&auth.login_with_device_code()?)?
+				}
+			};
+			println!("Signed in as {}", session.name);
+			session
+		}
+		(false, Some(account)) => {
+			let session =
+				accounts.get_session(&account, &MicrosoftAuth::new(Endpoints::load()?))?;
+			println!("Playing as {}", session.name);
+			session
+		}
+		(false, None) => {
+			AuthSession::offline(username.as_deref().unwrap_or(DEFAULT_USERNAME))?
+		}
+	};
+
+	println!("\nUpdating version {version_id}. . .");
+	let version = Minecraft::new(
+		data_dir.clone(),
+		instance_dir.clone(),
+		&version_id,
+		modloader::get(instance.loader.as_deref())?,
+		&manifest,
+	)?;
+	// Checking it before downloading hundreds of megabytes
+	let java = match instance.java_version {
+		// Mojang's runtime is picked by component, which is bound to version package
+		Some(major_version) if major_version != version.get_java_major_version() => {
+			select_java(&java_discovery, &runtime_installer, major_version, "")?
+		}
+		_ => select_java(
+			&java_discovery,
+			&runtime_installer,
+			version.get_java_major_version(),
+			version.get_java_component(),
+		)?,
+	};
+
+	// Taken before update, natives of running game shouldn't be replaced either
+	// Released on drop, even if update fails
+	let lock = InstanceLock::acquire(&instance_dir)?;
+	version.update(&java)?;
+
+	// Settings, that failed to install, aren't remembered
+	if instance_changed {
+		instance.save(&instance_dir)?;
+	}
+
+	if detach {
+		let pid = version.launch_detached(&java, &instance, &session)?;
+		lock.set_owner(pid)?;
+		lock.keep();
+		Registry::load(&data_dir)?.add(pid, &instance_dir, version.get_id())?;
+		println!("Game is started in background (PID {pid})");
+		return Ok(());
+	}
+
+	let started = SystemTime::now();
+	let game = version.launch(&java, &instance, &session)?;
+	let pid = game.get_pid();
+	let log_path = game.get_log_path().to_path_buf();
+	lock.set_owner(pid)?;
+	let entry = RegistryEntry::add(&data_dir, pid, &instance_dir, version.get_id())?;
+
+	// Bookkeeping failures are only reported, exit status of the game is what matters
+	let result = game.wait();
+	drop(entry);
+	drop(lock);
+
+	// Game's own report is more useful, than its output
+	// Analysis is best-effort, failure to read report shouldn't replace the game's error
+	if result.is_err() {
+		let report =
+			CrashReport::find_newest(&instance_dir, started).and_then(|report| match report {
+				Some(report) => Ok(report),
+				None => CrashReport::parse(&log_path),
+			});
+		match report {
+			Ok(report) => report.print_summary(),
+			Err(e) => eprintln!("Warning: failed to analyse crash: {e}"),
+		}
+	}
+
+	result
+}
+
+// account add|list|remove [name]|default [name]
+fn manage_accounts(
+	data_dir: &str,
+	arguments: &[&str],
+	auth_server: Option<&str>,
+) -> Result<(), Error> {
+	let mut accounts = AccountStore::load(data_dir)?;
+
+	match arguments {
+		["add"] => {
+			let (session, refresh_token) = match auth_server {
+				Some(url) => login_yggdrasil(url)?,
+				None => {
+					let auth = MicrosoftAuth::new(Endpoints::load()?);
+					let token = auth.login_with_device_code()?;
+					(auth.get_session(&token)?, token.refresh_token)
+				}
+			};
+
+			accounts.add(&session, &refresh_token)?;
+			println!("Added account {} ({})", session.name, session.uuid);
+		}
+		["list"] => {
+			for account in accounts.get_accounts() {
+				let mark = match accounts.is_default(account) {
+					true => "\tdefault",
+					false => "",
+				};
+				println!("{}\t{}{mark}", account.name, account.uuid);
+			}
+		}
+		["remove", name] => {
+			let account = accounts.remove(name)?;
+			println!("Removed account {}", account.name);
+		}
+		["default", name] => {
+			let account = accounts.set_default(name)?;
+			println!("Default account is {}", account.name);
+		}
+		_ => {
+			return Err(Error::Default(format!(
+				"unknown account command: {}. Use add, list, remove [name] or default [name]",
+				arguments.join(" ")
+			)))
+		}
+	}
+
+	Ok(())
+}
+
+// Newest crash report or launch log of instance is taken by default
+// Status goes to stderr, so translated text may be redirected to file
+fn deobfuscate(
+	version_id: &str,
+	instance: &Instance,
+	manifest: &Manifest,
+	instance_dir: &str,
+	path: Option<&str>,
+) -> Result<(), Error> {
+	let path = match path {
+		Some(path) => PathBuf::from(path),
+		None => match CrashReport::find_newest(instance_dir, UNIX_EPOCH)? {
+			Some(report) => report.path,
+			None => Supervisor::new(instance_dir, DEFAULT_KEPT_LOGS)
+				.get_logs()?
+				.pop()
+				.ok_or_else(|| {
+					Error::Default(format!("no crash reports or logs in {instance_dir}"))
+				})?,
+		},
+	};
+
+	// Logs may contain anything
+	let text = String::from_utf8_lossy(&fs::read(&path)?).into_owned();
+
+	// Instance may be switched to other version since the crash
+	let version_id = deobf::get_game_version(&text).unwrap_or(version_id);
+	if instance.loader.is_some() || deobf::is_modded(&text) {
+		eprintln!("Warning: game is modded, only vanilla names are translated. Fabric and older Forge use their own names, mappings don't know them");
+	}
+
+	let package = Vanilla::load(version_id, manifest)?;
+	let mappings_object = package.get_client_mappings_object()?;
+	eprintln!("Mappings of {}: {}", package.id, mappings_object.path);
+	Minecraft::download_objects(std::slice::from_ref(&mappings_object))?;
+
+	let mappings = Mappings::load(Path::new(&mappings_object.path))?;
+	if mappings.is_empty() {
+		return Err(Error::Default(format!(
+			"wrong mappings file {}",
+			mappings_object.path
+		)));
+	}
+
+	eprintln!("Deobfuscating {}. . .\n", path.display());
+	println!("{}", mappings.translate(&text));
 
 	Ok(())
 }
+
+// Session and client token of third-party auth server
+fn login_yggdrasil(url: &str) -> Result<(AuthSession, String), Error> {
+	let auth = YggdrasilAuth::resolve(url)?;
+	println!("Signing in to {}", auth.get_api_root());
+
+	let login = auth.authenticate(&ask("Login: ", false)?, &ask("Password: ", true)?)?;
+	if let Some(session) = login.session {
+		return Ok((session, login.client_token));
+	}
+
+	let profile = match &login.available_profiles[..] {
+		[] => {
+			return Err(Error::Default(String::from(
+				"this account has no players on auth server",
+			)))
+		}
+		[profile] => profile,
+		profiles => {
+			for (index, profile) in profiles.iter().enumerate() {
+				println!("{}) {}", index + 1, profile.name);
+			}
+			ask("Player: ", false)?
+				.parse::<usize>()
+				.ok()
+				.and_then(|number| profiles.get(number.wrapping_sub(1)))
+				.ok_or_else(|| Error::Default(String::from("no such player")))?
+		}
+	};
+
+	let session = auth.refresh(&login.access_token, &login.client_token, Some(profile))?;
+	Ok((session, login.client_token))
+}
+
+// Hidden input relies on "stty", it's shown as is without terminal
+fn ask(question: &str, hidden: bool) -> Result<String, Error> {
+	print!("{question}");
+	io::stdout().flush()?;
+
+	let hidden = hidden
+		&& Command::new("stty")
+			.arg("-echo")
+			.stderr(Stdio::null())
+			.status()
+			.is_ok_and(|status| status.success());
+	let mut answer = String::new();
+	let result = io::stdin().read_line(&mut answer);
+
+	if hidden {
+		Command::new("stty").arg("echo").status()?;
+		println!();
+	}
+	result?;
+
+	Ok(answer.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn select_java(
+	discovery: &JavaDiscovery,
+	installer: &RuntimeInstaller,
+	major_version: u32,
+	component: &str,
+) -> Result<JavaRuntime, Error> {
+	let java = discovery.select_or_install(major_version, component, installer)?;
+	println!(
+		"Using java {} ({})",
+		java.major_version,
+		java.executable.display()
+	);
+
+	Ok(java)
+}
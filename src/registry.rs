@@ -0,0 +1,514 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::error::Error;
+
+/* REGISTRY
+* data/running.json remembers games, that were started by launcher:
+* [ { "pid": 1234, "startTime": [ticks since boot], "instance": "/home/user/instances/Default", "version": "1.20.1", "startedAt": [unix time] }, ... ]
+* Game is running, while process with its PID exists, other entries are removed on load
+* PID of finished game may be reused by other process, so start time of process is compared too,
+* where it's known (linux, /proc/[pid]/stat)
+* File is exclusively locked while registry is loaded, so launchers don't overwrite each other's entries
+* [instance]/rostermine.lock keeps "[PID] [start time]" of launcher and then of game, so the same instance
+* isn't launched twice (worlds and options.txt would be corrupted). Empty lock is free
+* Lock file is exclusively locked while it's checked or changed, so only one launcher takes over stale lock
+* Lock and registry entry of attached game are released on drop, whatever way launcher stops waiting
+* Stopping asks game to exit (SIGTERM) and kills it, if it's still running after 10 seconds
+*/
+
+const REGISTRY_FILE: &str = "running.json";
+const LOCK_FILE: &str = "rostermine.lock";
+
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Holds lock of the file until it's dropped
+pub struct Registry {
+	file: File,
+	games: Vec<RunningGame>,
+}
+
+// Entry of game, that launcher waits for
+pub struct RegistryEntry {
+	data_dir: String,
+	pid: u32,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RunningGame {
+	pub pid: u32,
+	pub start_time: Option<u64>,
+	// Absolute path
+	pub instance: String,
+	pub version: String,
+	pub started_at: u64,
+}
+
+pub struct InstanceLock {
+	path: PathBuf,
+	kept: bool,
+}
+
+impl Registry {
+	// Finished games are forgotten
+	pub fn load(data_dir: &str) -> Result<Self, Error> {
+		fs::create_dir_all(data_dir)?;
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(Path::new(data_dir).join(REGISTRY_FILE))?;
+		file.lock()?;
+
+		let mut text = String::new();
+		file.read_to_string(&mut text)?;
+		let games: Vec<RunningGame> = match text.trim().is_empty() {
+			true => Default::default(),
+			false => serde_json::from_str(&text)?,
+		};
+		let count = games.len();
+
+		let mut registry = Self {
+			file,
+			games: games
+				.into_iter()
+				.filter(|game| is_running(game.pid, game.start_time))
+				.collect(),
+		};
+		if registry.games.len() != count {
+			registry.save()?;
+		}
+
+		Ok(registry)
+	}
+
+	fn save(&mut self) -> Result<(), Error> {
+		self.file.set_len(0)?;
+		self.file.rewind()?;
+		self.file
+			.write_all(serde_json::to_string_pretty(&self.games)?.as_bytes())?;
+
+		Ok(())
+	}
+
+	pub fn get_games(&self) -> &[RunningGame] {
+		&self.games
+	}
+
+	// By instance path or directory name
+	pub fn find(&self, instance: &str) -> Vec<RunningGame> {
+		let path = fs::canonicalize(instance)
+			.map(|path| path.to_string_lossy().into_owned())
+			.unwrap_or_default();
+
+		self.games
+			.iter()
+			.filter(|game| {
+				game.instance == path
+					|| Path::new(&game.instance)
+						.file_name()
+						.is_some_and(|name| name == instance)
+			})
+			.cloned()
+			.collect()
+	}
+
+	pub fn add(&mut self, pid: u32, instance_dir: &str, version: &str) -> Result<(), Error> {
+		let instance = fs::canonicalize(instance_dir)?;
+
+		self.games.push(RunningGame {
+			pid,
+			start_time: get_start_time(pid),
+			instance: instance.to_string_lossy().into_owned(),
+			version: version.to_string(),
+			started_at: get_time(),
+		});
+
+		self.save()
+	}
+
+	pub fn remove(&mut self, pid: u32) -> Result<(), Error> {
+		self.games.retain(|game| game.pid != pid);
+		self.save()
+	}
+}
+
+impl RegistryEntry {
+	pub fn add(data_dir: &str, pid: u32, instance_dir: &str, version: &str) -> Result<Self, Error> {
+		Registry::load(data_dir)?.add(pid, instance_dir, version)?;
+
+		Ok(Self {
+			data_dir: data_dir.to_string(),
+			pid,
+		})
+	}
+}
+
+// Registry may be changed by other launchers meanwhile, so it's loaded again
+impl Drop for RegistryEntry {
+	fn drop(&mut self) {
+		let removed =
+			Registry::load(&self.data_dir).and_then(|mut registry| registry.remove(self.pid));
+		if let Err(e) = removed {
+			eprintln!(
+				"Warning: failed to remove game {} from registry: {e}",
+				self.pid
+			);
+		}
+	}
+}
+
+impl RunningGame {
+	// Seconds since start
+	pub fn get_uptime(&self) -> u64 {
+		get_time().saturating_sub(self.started_at)
+	}
+
+	// Graceful first, so game is able to save worlds
+	pub fn stop(&self) -> Result<(), Error> {
+		// Other process shouldn't be stopped instead of finished game
+		if !is_running(self.pid, self.start_time) {
+			return Ok(());
+		}
+		send_signal(self.pid, false)?;
+
+		let mut waited = Duration::ZERO;
+		while waited < STOP_TIMEOUT {
+			if !is_running(self.pid, self.start_time) {
+				return Ok(());
+			}
+			thread::sleep(STOP_POLL_INTERVAL);
+			waited += STOP_POLL_INTERVAL;
+		}
+
+		println!("Game {} doesn't stop, killing it. . .", self.pid);
+		send_signal(self.pid, true)
+	}
+}
+
+impl InstanceLock {
+	// Lock of finished game or crashed launcher is taken over
+	pub fn acquire(instance_dir: &str) -> Result<Self, Error> {
+		fs::create_dir_all(instance_dir)?;
+		let path = Path::new(instance_dir).join(LOCK_FILE);
+		let mut file = Self::open(&path)?;
+
+		let mut owner = String::new();
+		file.read_to_string(&mut owner)?;
+		if let Some((pid, _)) =
+			parse_owner(&owner).filter(|&(pid, start_time)| is_running(pid, start_time))
+		{
+			return Err(Error::Default(format!(
+				"instance {instance_dir} is already running (PID {pid}). Stop it with \"stop [instance]\""
+			)));
+		}
+
+		Self::write_owner(&mut file, process::id())?;
+		Ok(Self { path, kept: false })
+	}
+
+	// Detached game keeps lock after launcher exits
+	pub fn set_owner(&self, pid: u32) -> Result<(), Error> {
+		Self::write_owner(&mut Self::open(&self.path)?, pid)
+	}
+
+	// Lock stays, until detached game finishes
+	pub fn keep(mut self) {
+		self.kept = true;
+	}
+
+	// Released, when file is closed
+	fn open(path: &Path) -> Result<File, Error> {
+		let file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(path)?;
+		file.lock()?;
+
+		Ok(file)
+	}
+
+	fn write_owner(file: &mut File, pid: u32) -> Result<(), Error> {
+		file.set_len(0)?;
+		file.rewind()?;
+		match get_start_time(pid) {
+			Some(start_time) => write!(file, "{pid} {start_time}")?,
+			None => write!(file, "{pid}")?,
+		}
+
+		Ok(())
+	}
+}
+
+impl Drop for InstanceLock {
+	fn drop(&mut self) {
+		if self.kept {
+			return;
+		}
+		// File stays, other launcher may be waiting to lock it
+		let released = Self::open(&self.path).and_then(|file| Ok(file.set_len(0)?));
+		if let Err(e) = released {
+			eprintln!(
+				"Warning: failed to release lock {}: {e}",
+				self.path.display()
+			);
+		}
+	}
+}
+
+fn get_time() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|time| time.as_secs())
+		.unwrap_or_default()
+}
+
+// "[PID] [start time]" or just "[PID]"
+fn parse_owner(text: &str) -> Option<(u32, Option<u64>)> {
+	let mut values = text.split_whitespace();
+	let pid = values.next()?.parse().ok()?;
+
+	Some((pid, values.next().and_then(|time| time.parse().ok())))
+}
+
+// Process with the same PID, which started at other time, is other one
+fn is_running(pid: u32, start_time: Option<u64>) -> bool {
+	is_alive(pid)
+		&& match (start_time, get_start_time(pid)) {
+			(Some(recorded), Some(current)) => recorded == current,
+			_ => true,
+		}
+}
+
+// Clock ticks since boot
+#[cfg(target_os = "linux")]
+fn get_start_time(pid: u32) -> Option<u64> {
+	parse_start_time(&fs::read_to_string(format!("/proc/{pid}/stat")).ok()?)
+}
+// Unknown, PID alone is trusted
+#[cfg(not(target_os = "linux"))]
+fn get_start_time(_pid: u32) -> Option<u64> {
+	None
+}
+
+// "[pid] ([name]) [state] ...", name may contain spaces and parentheses
+// Start time is 22nd field
+#[cfg(any(target_os = "linux", test))]
+fn parse_start_time(stat: &str) -> Option<u64> {
+	let (_, fields) = stat.rsplit_once(')')?;
+	// First field after name is 3rd one
+	fields.split_whitespace().nth(22 - 3)?.parse().ok()
+}
+
+// Zero and negative values mean process groups for kill()
+#[cfg(unix)]
+fn get_process_id(pid: u32) -> Option<libc::pid_t> {
+	libc::pid_t::try_from(pid).ok().filter(|&pid| pid > 0)
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+	let Some(pid) = get_process_id(pid) else {
+		return false;
+	};
+
+	// Signal 0 only checks, that process exists
+	let result = unsafe { libc::kill(pid, 0) };
+	result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+#[cfg(not(unix))]
+fn is_alive(pid: u32) -> bool {
+	process::Command::new("tasklist")
+		.args(["/FI", &format!("PID eq {pid}"), "/FO", "CSV", "/NH"])
+		.output()
+		.is_ok_and(|output| is_listed(&String::from_utf8_lossy(&output.stdout), pid))
+}
+
+// "[image name]","[PID]","[session name]",... per line, message without quotes when nothing is found
+#[cfg(any(not(unix), test))]
+fn is_listed(tasklist: &str, pid: u32) -> bool {
+	tasklist.lines().any(|line| {
+		line.trim()
+			.strip_prefix('"')
+			.and_then(|line| line.split("\",\"").nth(1))
+			.is_some_and(|column| column.parse::<u32>() == Ok(pid))
+	})
+}
+
+#[cfg(unix)]
+fn send_signal(pid: u32, force: bool) -> Result<(), Error> {
+	let signal = match force {
+		true => libc::SIGKILL,
+		false => libc::SIGTERM,
+	};
+
+	let process_id =
+		get_process_id(pid).ok_or_else(|| Error::Default(format!("wrong process id {pid}")))?;
+
+	match unsafe { libc::kill(process_id, signal) } {
+		-1 => Err(std::io::Error::last_os_error().into()),
+		_ => Ok(()),
+	}
+}
+#[cfg(not(unix))]
+fn send_signal(pid: u32, force: bool) -> Result<(), Error> {
+	let mut command = process::Command::new("taskkill");
+	command.args(["/PID", &pid.to_string()]);
+	if force {
+		command.arg("/F");
+	}
+	command.stdout(process::Stdio::null()).status()?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::util::testing::get_temp_dir;
+
+	#[test]
+	fn finds_exact_pid_in_tasklist() {
+		let tasklist = "\"java.exe\",\"12345\",\"Console\",\"1\",\"1,024 K\"\r\n";
+
+		assert!(is_listed(tasklist, 12345));
+		assert!(!is_listed(tasklist, 1234));
+		assert!(!is_listed(tasklist, 1));
+		assert!(!is_listed(
+			"INFO: No tasks are running which match the specified criteria.",
+			1234
+		));
+	}
+
+	#[test]
+	fn entry_is_removed_on_drop() {
+		let data_dir = get_temp_dir("registry-entry");
+		let data_dir = data_dir.to_str().unwrap();
+		let pid = process::id();
+
+		let entry = RegistryEntry::add(data_dir, pid, data_dir, "1.20.1").unwrap();
+		assert_eq!(Registry::load(data_dir).unwrap().get_games()[0].pid, pid);
+
+		drop(entry);
+		assert!(Registry::load(data_dir).unwrap().get_games().is_empty());
+	}
+
+	#[test]
+	fn lock_is_released_on_drop() {
+		let instance_dir = get_temp_dir("registry-lock");
+		let instance_dir = instance_dir.to_str().unwrap();
+		let path = Path::new(instance_dir).join(LOCK_FILE);
+
+		let lock = InstanceLock::acquire(instance_dir).unwrap();
+		assert!(InstanceLock::acquire(instance_dir).is_err());
+		drop(lock);
+		assert!(fs::read_to_string(&path).unwrap().is_empty());
+
+		InstanceLock::acquire(instance_dir).unwrap().keep();
+		assert_eq!(
+			parse_owner(&fs::read_to_string(path).unwrap()).unwrap().0,
+			process::id()
+		);
+	}
+
+	#[test]
+	fn reads_start_time_after_name() {
+		let stat =
+			"1234 (java) R) S 1 1234 1234 0 -1 4194560 100 0 0 0 5 1 0 0 20 0 40 0 987654 1000 100";
+		assert_eq!(parse_start_time(stat), Some(987654));
+		assert_eq!(parse_start_time("1234 (java) S 1"), None);
+
+		assert_eq!(parse_owner("1234 987654"), Some((1234, Some(987654))));
+		// Written by older launcher
+		assert_eq!(parse_owner("1234\n"), Some((1234, None)));
+		assert_eq!(parse_owner(""), None);
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn reused_pid_is_not_running() {
+		let pid = process::id();
+		let start_time = get_start_time(pid).unwrap();
+
+		assert!(is_running(pid, Some(start_time)));
+		assert!(is_running(pid, None));
+		assert!(!is_running(pid, Some(start_time + 1)));
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn game_with_reused_pid_is_forgotten() {
+		let data_dir = get_temp_dir("registry-reused");
+		let data_dir = data_dir.to_str().unwrap();
+		let pid = process::id();
+
+		let mut registry = Registry::load(data_dir).unwrap();
+		registry.add(pid, data_dir, "1.20.1").unwrap();
+		registry.games[0].start_time = registry.games[0].start_time.map(|time| time + 1);
+		registry.save().unwrap();
+		drop(registry);
+
+		assert!(Registry::load(data_dir).unwrap().get_games().is_empty());
+
+		// It's not stopped either
+		let game = RunningGame {
+			pid,
+			start_time: get_start_time(pid).map(|time| time + 1),
+			..Default::default()
+		};
+		game.stop().unwrap();
+	}
+
+	#[test]
+	fn stale_lock_is_taken_over() {
+		let instance_dir = get_temp_dir("registry-stale");
+		let instance_dir = instance_dir.to_str().unwrap();
+		let path = Path::new(instance_dir).join(LOCK_FILE);
+
+		// Beyond any PID limit
+		fs::write(&path, i32::MAX.to_string()).unwrap();
+		drop(InstanceLock::acquire(instance_dir).unwrap());
+
+		#[cfg(target_os = "linux")]
+		{
+			let start_time = get_start_time(process::id()).unwrap();
+			fs::write(&path, format!("{} {}", process::id(), start_time + 1)).unwrap();
+			drop(InstanceLock::acquire(instance_dir).unwrap());
+		}
+	}
+
+	#[test]
+	fn stale_lock_is_taken_over_once() {
+		let instance_dir = get_temp_dir("registry-race");
+		let instance_dir = instance_dir.to_str().unwrap().to_string();
+		fs::write(
+			Path::new(&instance_dir).join(LOCK_FILE),
+			i32::MAX.to_string(),
+		)
+		.unwrap();
+
+		let launchers: Vec<_> = (0..8)
+			.map(|_| {
+				let instance_dir = instance_dir.clone();
+				thread::spawn(move || InstanceLock::acquire(&instance_dir).map(InstanceLock::keep))
+			})
+			.collect();
+		let acquired = launchers
+			.into_iter()
+			.map(|launcher| launcher.join().unwrap())
+			.filter(Result::is_ok)
+			.count();
+		assert_eq!(acquired, 1);
+	}
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fetch::minecraft::Minecraft;
+use crate::fetch::vanilla::{DataObject, Manifest, Vanilla};
+use crate::java::JavaRuntime;
+use crate::util::error::Error;
+
+/* SERVER
+* Dedicated server is a single jar from "downloads.server" of version package
+* It is stored in data/libraries/net/minecraft/server/[id]/server-[id]-official.jar
+* and launched inside of server directory, where installed version is remembered
+* Server refuses to start until eula.txt has "eula=true"
+*/
+
+const SERVER_FILE: &str = "rostermine-server.json";
+const EULA_FILE: &str = "eula.txt";
+const EULA_URL: &str = "https://aka.ms/MinecraftEULA";
+
+pub const DEFAULT_SERVER_MEMORY: &str = "2G";
+
+pub struct Server {
+	package: Vanilla,
+	server_dir: PathBuf,
+	libraries_dir: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct ServerInfo {
+	version: String,
+}
+
+impl Server {
+	pub fn new(
+		data_dir: &str,
+		server_dir: &str,
+		version_id: &str,
+		manifest: &Manifest,
+	) -> Result<Self, Error> {
+		Ok(Self {
+			package: Vanilla::load(version_id, manifest)?,
+			server_dir: PathBuf::from(server_dir),
+			libraries_dir: format!("{data_dir}/libraries"),
+		})
+	}
+
+	// Version, that was installed into server directory
+	pub fn open(data_dir: &str, server_dir: &str, manifest: &Manifest) -> Result<Self, Error> {
+		let path = Path::new(server_dir).join(SERVER_FILE);
+		if !path.exists() {
+			return Err(Error::Default(format!(
+				"no server installed in {server_dir}. Use \"server install [version]\" first"
+			)));
+		}
+
+		let info: ServerInfo = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+		Self::new(data_dir, server_dir, &info.version, manifest)
+	}
+
+	pub fn get_id(&self) -> &str {
+		&self.package.id
+	}
+
+	pub fn get_java_major_version(&self) -> u32 {
+		self.package.java_version.major_version.max(0) as u32
+	}
+
+	pub fn get_java_component(&self) -> &str {
+		&self.package.java_version.component
+	}
+
+	pub fn install(&self) -> Result<(), Error> {
+		Minecraft::download_objects(&[self.get_server_object()?])?;
+
+		fs::create_dir_all(&self.server_dir)?;
+		fs::write(
+			self.server_dir.join(SERVER_FILE),
+			serde_json::to_string_pretty(&ServerInfo {
+				version: self.package.id.clone(),
+			})?,
+		)?;
+
+		Ok(())
+	}
+
+	pub fn run(&self, java: &JavaRuntime, memory: &str) -> Result<(), Error> {
+		let server = self.get_server_object()?;
+		// Jar may be removed from data dir since installation
+		Minecraft::download_objects(std::slice::from_ref(&server))?;
+
+		self.accept_eula()?;
+
+		let jar = fs::canonicalize(&server.path)?;
+
+		Command::new(&java.executable)
+			.current_dir(&self.server_dir)
+			.arg(format!("-Xms{memory}"))
+			.arg(format!("-Xmx{memory}"))
+			.arg("-jar")
+			.arg(jar)
+			.arg("nogui")
+			.spawn()?
+			.wait()?;
+
+		Ok(())
+	}
+
+	fn get_server_object(&self) -> Result<DataObject, Error> {
+		let server = self.package.downloads.get("server").ok_or_else(|| {
+			Error::Default(format!(
+				"version {} has no dedicated server",
+				self.package.id
+			))
+		})?;
+
+		Ok(DataObject {
+			path: format!(
+				"{}/net/minecraft/server/{}/server-{}-official.jar",
+				self.libraries_dir, self.package.id, self.package.id
+			),
+			..server.clone()
+		})
+	}
+
+	fn accept_eula(&self) -> Result<(), Error> {
+		let path = self.server_dir.join(EULA_FILE);
+
+		let accepted = fs::read_to_string(&path)
+			.map(|text| text.lines().any(|line| line.trim() == "eula=true"))
+			.unwrap_or(false);
+		if accepted {
+			return Ok(());
+		}
+
+		print!("Do you accept Minecraft EULA ({EULA_URL})? [y/N]: ");
+		io::stdout().flush()?;
+
+		let mut answer = String::new();
+		io::stdin().read_line(&mut answer)?;
+
+		if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+			return Err(Error::Default(String::from(
+				"server can't be started without accepting EULA",
+			)));
+		}
+
+		fs::create_dir_all(&self.server_dir)?;
+		fs::write(
+			path,
+			format!("# Accepted via rostermine, see {EULA_URL}\neula=true\n"),
+		)?;
+
+		Ok(())
+	}
+}
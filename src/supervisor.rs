@@ -0,0 +1,364 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::util::error::Error;
+
+/* SUPERVISOR
+* Game output is shown as usual and copied into [instance]/logs/launcher/launch-[unix ms].log
+* Only last logs are kept: 10 by default, "keepLogs" in instance.json
+* Exit code (or signal) is reported, crashed game makes launcher exit with error too,
+* so scripts are able to notice it
+* Detached game gets own session (and process group), so closing terminal doesn't stop it,
+* and writes output straight into log
+*/
+
+pub const DEFAULT_KEPT_LOGS: usize = 10;
+
+const LOGS_DIR: &str = "logs/launcher";
+const LOG_PREFIX: &str = "launch-";
+
+pub struct Supervisor {
+	logs_dir: PathBuf,
+	keep_logs: usize,
+}
+
+// Game, which output is being copied
+pub struct SupervisedGame {
+	child: Child,
+	copiers: Vec<JoinHandle<()>>,
+	log_path: PathBuf,
+}
+
+impl Supervisor {
+	pub fn new(instance_dir: &str, keep_logs: usize) -> Self {
+		Self {
+			logs_dir: Path::new(instance_dir).join(LOGS_DIR),
+			// Log of current launch is always kept
+			keep_logs: keep_logs.max(1),
+		}
+	}
+
+	// Older logs are removed, so there are no more than N with the new one
+	pub fn create_log(&self) -> Result<(PathBuf, File), Error> {
+		fs::create_dir_all(&self.logs_dir)?;
+
+		let mut logs = self.get_logs()?;
+		while logs.len() >= self.keep_logs {
+			fs::remove_file(logs.remove(0))?;
+		}
+
+		let time = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|time| time.as_millis())
+			.unwrap_or_default();
+		let path = self.logs_dir.join(format!("{LOG_PREFIX}{time}.log"));
+
+		Ok((path.clone(), File::create(path)?))
+	}
+
+	// Oldest first
+	pub fn get_logs(&self) -> Result<Vec<PathBuf>, Error> {
+		if !self.logs_dir.exists() {
+			return Ok(Default::default());
+		}
+
+		let mut logs: Vec<PathBuf> = fs::read_dir(&self.logs_dir)?
+			.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| {
+				path.file_name()
+					.and_then(|name| name.to_str())
+					.is_some_and(|name| name.starts_with(LOG_PREFIX) && name.ends_with(".log"))
+			})
+			.collect();
+		// Same digits count for centuries ahead, so names are sorted as numbers
+		logs.sort();
+
+		Ok(logs)
+	}
+
+	pub fn spawn(&self, command: &mut Command) -> Result<SupervisedGame, Error> {
+		let (log_path, log) = self.create_log()?;
+		println!("Game log: {}", log_path.display());
+
+		let mut child = command
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()?;
+
+		let log = Arc::new(Mutex::new(log));
+		let copiers = [
+			child
+				.stdout
+				.take()
+				.map(|stdout| Self::tee(stdout, io::stdout, log.clone())),
+			child
+				.stderr
+				.take()
+				.map(|stderr| Self::tee(stderr, io::stderr, log.clone())),
+		]
+		.into_iter()
+		.flatten()
+		.collect();
+
+		Ok(SupervisedGame {
+			child,
+			copiers,
+			log_path,
+		})
+	}
+
+	pub fn spawn_detached(&self, command: &mut Command) -> Result<u32, Error> {
+		let (path, log) = self.create_log()?;
+		println!("Game log: {}", path.display());
+
+		command
+			.stdin(Stdio::null())
+			.stdout(log.try_clone()?)
+			.stderr(log);
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::process::CommandExt;
+
+			// Only async-signal-safe calls are allowed between fork and exec
+			unsafe {
+				command.pre_exec(|| match libc::setsid() {
+					-1 => Err(io::Error::last_os_error()),
+					_ => Ok(()),
+				});
+			}
+		}
+		#[cfg(windows)]
+		{
+			use std::os::windows::process::CommandExt;
+
+			const DETACHED_PROCESS: u32 = 0x00000008;
+			const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+			command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+		}
+
+		// Nobody waits for it here, orphaned game is reaped by init
+		Ok(command.spawn()?.id())
+	}
+
+	pub fn describe(status: &ExitStatus) -> String {
+		#[cfg(unix)]
+		{
+			use std::os::unix::process::ExitStatusExt;
+
+			if let Some(signal) = status.signal() {
+				let name = match signal {
+					6 => " (SIGABRT)",
+					9 => " (SIGKILL)",
+					11 => " (SIGSEGV)",
+					15 => " (SIGTERM)",
+					_ => "",
+				};
+				return format!("was killed by signal {signal}{name}");
+			}
+		}
+
+		match status.code() {
+			Some(0) => String::from("exited normally"),
+			Some(code) if Supervisor::is_stopped(code) => format!("was stopped (exit code {code})"),
+			Some(code) => format!("crashed with exit code {code}"),
+			None => String::from("exited with unknown status"),
+		}
+	}
+
+	// Crash is an error, stopped game isn't
+	fn check(status: &ExitStatus, log_path: &Path) -> Result<(), Error> {
+		match status.success() || status.code().is_some_and(Supervisor::is_stopped) {
+			true => Ok(()),
+			false => Err(Error::Default(format!(
+				"game {}. See {}",
+				Supervisor::describe(status),
+				log_path.display()
+			))),
+		}
+	}
+
+	// JVM exits with 128 + signal number after SIGINT or SIGTERM, it's not a crash
+	fn is_stopped(code: i32) -> bool {
+		code == 130 || code == 143
+	}
+
+	// Lines go to terminal and log, bytes aren't required to be valid UTF-8
+	fn tee<R, W>(stream: R, terminal: fn() -> W, log: Arc<Mutex<File>>) -> JoinHandle<()>
+	where
+		R: Read + Send + 'static,
+		W: Write + 'static,
+	{
+		thread::spawn(move || {
+			let mut reader = BufReader::new(stream);
+			let mut line = Vec::new();
+
+			while reader
+				.read_until(b'\n', &mut line)
+				.is_ok_and(|size| size > 0)
+			{
+				let _ = terminal().write_all(&line);
+				if let Ok(mut log) = log.lock() {
+					let _ = log.write_all(&line);
+				}
+				line.clear();
+			}
+		})
+	}
+}
+
+impl SupervisedGame {
+	pub fn get_pid(&self) -> u32 {
+		self.child.id()
+	}
+
+	pub fn get_log_path(&self) -> &Path {
+		&self.log_path
+	}
+
+	pub fn wait(mut self) -> Result<(), Error> {
+		let status = self.child.wait()?;
+		// Output is read till the end, even if game left children with the same pipes
+		for copier in self.copiers {
+			let _ = copier.join();
+		}
+
+		println!("\nGame {}", Supervisor::describe(&status));
+		Supervisor::check(&status, &self.log_path)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::util::testing::get_temp_dir;
+
+	fn get_names(logs: &[PathBuf]) -> Vec<String> {
+		logs.iter()
+			.map(|log| log.file_name().unwrap().to_string_lossy().into_owned())
+			.collect()
+	}
+
+	#[test]
+	fn only_last_logs_are_kept() {
+		let instance_dir = get_temp_dir("supervisor-rotation");
+		let supervisor = Supervisor::new(instance_dir.to_str().unwrap(), 3);
+
+		let logs_dir = instance_dir.join(LOGS_DIR);
+		fs::create_dir_all(&logs_dir).unwrap();
+		for time in [
+			1000000000003u64,
+			1000000000001,
+			1000000000002,
+			1000000000004,
+		] {
+			fs::write(logs_dir.join(format!("{LOG_PREFIX}{time}.log")), "").unwrap();
+		}
+		// Other files are left alone
+		fs::write(logs_dir.join("latest.log"), "").unwrap();
+
+		let (path, _) = supervisor.create_log().unwrap();
+		let logs = supervisor.get_logs().unwrap();
+
+		assert_eq!(
+			get_names(&logs[..2]),
+			["launch-1000000000003.log", "launch-1000000000004.log"]
+		);
+		assert_eq!(logs.len(), 3);
+		assert_eq!(logs[2], path);
+		assert!(logs_dir.join("latest.log").exists());
+	}
+
+	#[test]
+	fn current_log_is_always_kept() {
+		let instance_dir = get_temp_dir("supervisor-keep-none");
+		let supervisor = Supervisor::new(instance_dir.to_str().unwrap(), 0);
+
+		let logs_dir = instance_dir.join(LOGS_DIR);
+		fs::create_dir_all(&logs_dir).unwrap();
+		fs::write(logs_dir.join(format!("{LOG_PREFIX}1000000000000.log")), "").unwrap();
+
+		let (path, _) = supervisor.create_log().unwrap();
+		assert_eq!(supervisor.get_logs().unwrap(), [path]);
+	}
+
+	#[cfg(unix)]
+	mod status {
+		use std::os::unix::process::ExitStatusExt;
+
+		use super::*;
+
+		// Raw wait status keeps exit code in the second byte, signal in the first one
+		fn exited(code: i32) -> ExitStatus {
+			ExitStatus::from_raw(code << 8)
+		}
+
+		fn killed(signal: i32) -> ExitStatus {
+			ExitStatus::from_raw(signal)
+		}
+
+		#[test]
+		fn describes_exit() {
+			assert_eq!(Supervisor::describe(&exited(0)), "exited normally");
+			assert_eq!(Supervisor::describe(&exited(1)), "crashed with exit code 1");
+			assert_eq!(
+				Supervisor::describe(&exited(130)),
+				"was stopped (exit code 130)"
+			);
+			assert_eq!(
+				Supervisor::describe(&exited(143)),
+				"was stopped (exit code 143)"
+			);
+		}
+
+		#[test]
+		fn describes_signal() {
+			assert_eq!(
+				Supervisor::describe(&killed(9)),
+				"was killed by signal 9 (SIGKILL)"
+			);
+			assert_eq!(
+				Supervisor::describe(&killed(11)),
+				"was killed by signal 11 (SIGSEGV)"
+			);
+			assert_eq!(Supervisor::describe(&killed(10)), "was killed by signal 10");
+		}
+
+		#[test]
+		fn only_crash_is_error() {
+			let log_path = Path::new("launch.log");
+
+			assert!(Supervisor::check(&exited(0), log_path).is_ok());
+			assert!(Supervisor::check(&exited(130), log_path).is_ok());
+			assert!(Supervisor::check(&exited(143), log_path).is_ok());
+
+			let error = Supervisor::check(&exited(1), log_path).unwrap_err();
+			assert!(error.to_string().contains("crashed with exit code 1"));
+			assert!(error.to_string().contains("launch.log"));
+			assert!(Supervisor::check(&killed(11), log_path).is_err());
+		}
+
+		#[test]
+		fn output_is_copied_into_log() {
+			let instance_dir = get_temp_dir("supervisor-wait");
+			let supervisor = Supervisor::new(instance_dir.to_str().unwrap(), 1);
+
+			let game = supervisor
+				.spawn(Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+				.unwrap();
+			let log_path = game.get_log_path().to_path_buf();
+
+			let error = game.wait().unwrap_err();
+			assert!(error.to_string().contains("crashed with exit code 3"));
+
+			let log = fs::read_to_string(log_path).unwrap();
+			assert!(log.contains("out\n"));
+			assert!(log.contains("err\n"));
+		}
+	}
+}
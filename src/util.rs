@@ -1 +1,4 @@
-pub mod error;
\ No newline at end of file
+pub mod error;
+
+#[cfg(test)]
+pub mod testing;
\ No newline at end of file
@@ -13,4 +13,7 @@ pub enum Error {
 
 	#[error("json parsing: {0}")]
 	JSONParse(#[from] serde_json::Error),
+
+	#[error("archive extraction: {0}")]
+	Zip(#[from] zip::result::ZipError),
 }
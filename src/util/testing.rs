@@ -0,0 +1,144 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/* TESTING
+* Local stand-ins of remote servers: plain HTTP/1.1 on 127.0.0.1 with random port,
+* every connection gets one response and is closed
+* Requests are remembered, so tests are able to check what was sent
+*/
+
+#[derive(Default, Debug, Clone)]
+pub struct Request {
+	pub path: String,
+	pub headers: Vec<(String, String)>,
+	pub body: String,
+}
+
+#[derive(Default, Debug)]
+pub struct Response {
+	pub status: u16,
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>,
+}
+
+pub struct TestServer {
+	url: String,
+	requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Response {
+	pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+		Self {
+			status,
+			headers: Default::default(),
+			body: body.into(),
+		}
+	}
+
+	pub fn json(status: u16, body: serde_json::Value) -> Self {
+		Self::new(status, body.to_string()).with_header("Content-Type", "application/json")
+	}
+
+	pub fn not_found() -> Self {
+		Self::new(404, "")
+	}
+
+	pub fn with_header(mut self, name: &str, value: &str) -> Self {
+		self.headers.push((name.to_string(), value.to_string()));
+		self
+	}
+}
+
+impl TestServer {
+	pub fn start(handler: impl Fn(&Request) -> Response + Send + 'static) -> Self {
+		let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind test server");
+		let url = format!("http://{}", listener.local_addr().unwrap());
+		let requests: Arc<Mutex<Vec<Request>>> = Default::default();
+
+		let log = requests.clone();
+		// Thread lives until tests finish
+		thread::spawn(move || {
+			for stream in listener.incoming().flatten() {
+				let Some(request) = Self::read_request(&stream) else {
+					continue;
+				};
+				let response = handler(&request);
+				log.lock().unwrap().push(request);
+				let _ = Self::write_response(stream, &response);
+			}
+		});
+
+		Self { url, requests }
+	}
+
+	pub fn get_url(&self) -> &str {
+		&self.url
+	}
+
+	pub fn get_requests(&self) -> Vec<Request> {
+		self.requests.lock().unwrap().clone()
+	}
+
+	fn read_request(stream: &TcpStream) -> Option<Request> {
+		let mut reader = BufReader::new(stream);
+
+		let mut line = String::new();
+		reader.read_line(&mut line).ok()?;
+		let mut request = Request {
+			path: line.split_whitespace().nth(1)?.to_string(),
+			..Default::default()
+		};
+
+		loop {
+			line.clear();
+			reader.read_line(&mut line).ok()?;
+			let Some((name, value)) = line.trim_end().split_once(':') else {
+				break;
+			};
+			request
+				.headers
+				.push((name.trim().to_lowercase(), value.trim().to_string()));
+		}
+
+		let length = request
+			.headers
+			.iter()
+			.find(|(name, _)| name == "content-length")
+			.and_then(|(_, value)| value.parse::<usize>().ok())
+			.unwrap_or_default();
+		let mut body = vec![0; length];
+		reader.read_exact(&mut body).ok()?;
+		request.body = String::from_utf8_lossy(&body).into_owned();
+
+		Some(request)
+	}
+
+	fn write_response(mut stream: TcpStream, response: &Response) -> std::io::Result<()> {
+		write!(
+			stream,
+			"HTTP/1.1 {} STATUS\r\nContent-Length: {}\r\nConnection: close\r\n",
+			response.status,
+			response.body.len()
+		)?;
+		for (name, value) in &response.headers {
+			write!(stream, "{name}: {value}\r\n")?;
+		}
+		stream.write_all(b"\r\n")?;
+		stream.write_all(&response.body)?;
+		stream.flush()
+	}
+}
+
+// Empty directory, unique for test
+pub fn get_temp_dir(name: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("rostermine-test-{}-{name}", std::process::id()));
+	if path.exists() {
+		fs::remove_dir_all(&path).unwrap();
+	}
+	fs::create_dir_all(&path).unwrap();
+	path
+}